@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use image::{imageops, RgbaImage};
+
+use crate::Board;
+
+/// Cell bounding box, inclusive: [x1, y1, x2, y2] starting at 1
+pub type CellArea = [u32; 4];
+
+pub fn full_area(board: &Board) -> CellArea {
+    [1, 1, board.grid_size[0], board.grid_size[1]]
+}
+
+pub fn is_full(board: &Board, area: CellArea) -> bool {
+    area == full_area(board)
+}
+
+pub fn merge(a: Option<CellArea>, b: CellArea) -> CellArea {
+    match a {
+        Some(a) => {[a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])]}
+        None => {b}
+    }
+}
+
+pub fn around(board: &Board, x: u32, y: u32, radius: u32) -> CellArea {
+    // cells within `radius` of (x, y), clamped to the grid
+    [
+        x.saturating_sub(radius).max(1),
+        y.saturating_sub(radius).max(1),
+        (x + radius).min(board.grid_size[0]),
+        (y + radius).min(board.grid_size[1]),
+    ]
+}
+
+pub fn on_inner_edge(board: &Board, area: CellArea, x: u32, y: u32) -> bool {
+    // on the edge of the area, but not on the edge of the grid, so the area could grow past it
+    (x == area[0] && x > 1)
+        || (y == area[1] && y > 1)
+        || (x == area[2] && x < board.grid_size[0])
+        || (y == area[3] && y < board.grid_size[1])
+}
+
+/// Timing of a single capture
+pub struct Sample {
+    time: Duration,
+    pixels: u64,
+    cells: u32,
+    full: bool,
+}
+
+#[derive(Default)]
+pub struct CaptureStats {
+    pub captures: u32,
+    pub full: u32, // captures of the whole board
+    pub total_time: Duration,
+    pub total_pixels: u64,
+    pub last_time: Duration,
+    pub last_pixels: u64,
+    pub last_cells: u32,
+}
+
+impl CaptureStats {
+    pub fn add(&mut self, sample: &Sample) {
+        self.captures += 1;
+        if sample.full {
+            self.full += 1;
+        }
+        self.total_time += sample.time;
+        self.total_pixels += sample.pixels;
+        self.last_time = sample.time;
+        self.last_pixels = sample.pixels;
+        self.last_cells = sample.cells;
+    }
+
+    pub fn last(&self) -> String {
+        format!(
+            "{} cells, {} px in {:.1}ms",
+            self.last_cells,
+            self.last_pixels,
+            self.last_time.as_secs_f64() * 1000.0,
+        )
+    }
+
+    pub fn summary(&self) -> String {
+        let captures = self.captures.max(1);
+        format!(
+            "{} captures ({} full) | avg {:.1}ms, {} px",
+            self.captures,
+            self.full,
+            self.total_time.as_secs_f64() * 1000.0 / captures as f64,
+            self.total_pixels / captures as u64,
+        )
+    }
+}
+
+pub fn capture(board: &Board, image: &mut RgbaImage, area: CellArea) -> Result<Sample, Box<dyn std::error::Error>> {
+    // captures the cells in `area` and pastes them into the full board image
+    let full = is_full(board, area);
+    let (left, top, width, height) = if full {
+        // whole board region, including the face/counters
+        (board.corners[0], board.corners[1], board.corners[2] - board.corners[0], board.corners[3] - board.corners[1])
+    } else {
+        (
+            board.inner_board_corner[0] + (area[0] - 1) * board.cell_size,
+            board.inner_board_corner[1] + (area[1] - 1) * board.cell_size,
+            (area[2] - area[0] + 1) * board.cell_size,
+            (area[3] - area[1] + 1) * board.cell_size,
+        )
+    };
+
+    let start = Instant::now();
+    let region = board.monitor.capture_region(left, top, width, height)?;
+    let time = start.elapsed();
+
+    imageops::replace(
+        image,
+        &region,
+        (left - board.corners[0]) as i64,
+        (top - board.corners[1]) as i64,
+    );
+    Ok(Sample {
+        time,
+        pixels: width as u64 * height as u64,
+        cells: (area[2] - area[0] + 1) * (area[3] - area[1] + 1),
+        full,
+    })
+}
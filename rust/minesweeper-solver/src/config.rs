@@ -0,0 +1,389 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+
+use crate::{
+    recognition::{Classifier, ClassifierKind, TemplateSet},
+    ChordMode, Skin,
+};
+
+pub const DEFAULT_CONFIG_PATH: &str = "solver.toml";
+
+#[derive(Parser, Debug)]
+#[command(about = "Automated minesweeper solver")]
+pub struct Cli {
+    /// Path to the config file
+    #[arg(short, long, default_value = DEFAULT_CONFIG_PATH)]
+    pub config: PathBuf,
+
+    /// Profile (game site) to use from the config file
+    #[arg(short, long)]
+    pub profile: Option<String>,
+
+    /// List the profiles in the config file and exit
+    #[arg(long)]
+    pub list_profiles: bool,
+
+    /// Board difficulty, sets grid size and mine count
+    #[arg(short, long, value_enum)]
+    pub difficulty: Option<Difficulty>,
+
+    /// Grid width in cells (custom difficulty)
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Grid height in cells (custom difficulty)
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Number of mines on the board (custom difficulty)
+    #[arg(short, long)]
+    pub mines: Option<u32>,
+
+    /// Maximum number of solver steps before giving up
+    #[arg(long)]
+    pub step_limit: Option<u32>,
+
+    /// Delay between solver steps, in ms
+    #[arg(long)]
+    pub wait_time: Option<u64>,
+
+    /// Delay after each mouse click, in ms
+    #[arg(long)]
+    pub click_delay: Option<u64>,
+
+    /// Seconds to count down before starting
+    #[arg(long)]
+    pub countdown: Option<u32>,
+
+    /// Number of games to play, clicking the reset face between games
+    #[arg(short, long)]
+    pub games: Option<u32>,
+
+    /// File to log per-game results to (.csv or .json)
+    #[arg(short, long)]
+    pub results: Option<String>,
+
+    /// File to write the solver's decisions to (.json)
+    #[arg(long)]
+    pub decisions: Option<String>,
+
+    /// Replay a decision log offline instead of solving
+    #[arg(long, value_name = "DECISIONS")]
+    pub replay: Option<PathBuf>,
+
+    /// Game to replay, defaults to the last lost game
+    #[arg(long, requires = "replay")]
+    pub replay_game: Option<u32>,
+
+    /// Print the board before every replayed decision
+    #[arg(long, requires = "replay")]
+    pub replay_boards: bool,
+
+    /// Capture the board and label cell templates for the profile's skin, then exit
+    #[arg(long)]
+    pub label: bool,
+
+    /// Always capture the whole board instead of only the cells that changed
+    #[arg(long)]
+    pub full_capture: bool,
+
+    /// Save every captured board region to screenshots/
+    #[arg(long, overrides_with = "no_screenshots")]
+    pub screenshots: bool,
+
+    /// Don't save captured board regions
+    #[arg(long)]
+    pub no_screenshots: bool,
+
+    /// Don't draw the board, only print the final result
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Draw the board and print extra capture/debug info
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Beginner, // 9x9, 10 mines
+    Intermediate, // 16x16, 40 mines
+    Expert, // 30x16, 99 mines
+    Custom, // uses grid_size and mines from the profile
+}
+
+impl Difficulty {
+    fn preset(&self) -> Option<([u32; 2], u32)> {
+        match self {
+            Difficulty::Beginner => {Some(([9, 9], 10))}
+            Difficulty::Intermediate => {Some(([16, 16], 40))}
+            Difficulty::Expert => {Some(([30, 16], 99))}
+            Difficulty::Custom => {None}
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayMode {
+    Quiet, // no board, only the final result
+    Normal, // live board + status line
+    Verbose, // live board + status line + capture/debug info
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SkinConfig {
+    pub chord: ChordMode,
+    pub chord_min_saved: u32,
+    pub classifier: ClassifierKind,
+    pub templates: String, // template folder, defaults to templates/<skin name>
+    pub match_threshold: f64, // minimum template correlation (0-1)
+}
+
+impl Default for SkinConfig {
+    fn default() -> Self {
+        let skin = Skin::default_skin();
+        SkinConfig {
+            chord: skin.chord,
+            chord_min_saved: skin.chord_min_saved,
+            classifier: ClassifierKind::Pixel,
+            templates: String::new(),
+            match_threshold: skin.match_threshold,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub corners: [u32; 4], // top left, bottom right of the board, in screen px
+    pub inner_board_corner: [u32; 2], // top left corner of the cell grid
+    pub cell_size: u32,
+    pub face: [u32; 2], // reset face button, in screen px
+    pub difficulty: Difficulty,
+    pub grid_size: [u32; 2], // only used for custom difficulty
+    pub mines: u32, // only used for custom difficulty
+    pub skin: String, // name of a [skins.<name>] table
+    pub step_limit: u32,
+    pub wait_time: u64, // ms between solver steps
+    pub click_delay: u64, // ms after each click
+    pub countdown: u32, // seconds before starting
+    pub games: u32, // games to play in a session
+    pub restart_delay: u64, // ms to wait after clicking the reset face
+    pub results_log: String, // .csv or .json file for per-game results, empty to disable
+    pub decision_log: String, // .json file for every decision the solver makes, empty to disable
+    pub save_screenshots: bool,
+    pub incremental_capture: bool, // only capture the cells touched by the last actions
+    pub display: DisplayMode,
+    pub mouse_tolerance: u32, // px, pause if the mouse moves this far from where the solver left it, 0 to disable
+    pub failsafe_margin: u32, // px, stop if the mouse is this close to a screen corner, 0 to disable
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            corners: [1173, 308, 2433, 1111],
+            inner_board_corner: [1203, 443],
+            cell_size: 40,
+            face: [1803, 375],
+            difficulty: Difficulty::Custom,
+            grid_size: [30, 16],
+            mines: 50,
+            skin: String::from("default"),
+            step_limit: 500,
+            wait_time: 500,
+            click_delay: 50,
+            countdown: 2,
+            games: 1,
+            restart_delay: 1000,
+            results_log: String::from("results.csv"),
+            decision_log: String::from("decisions.json"),
+            save_screenshots: true,
+            incremental_capture: true,
+            display: DisplayMode::Normal,
+            mouse_tolerance: 10,
+            failsafe_margin: 5,
+        }
+    }
+}
+
+impl Profile {
+    pub fn grid_size(&self) -> [u32; 2] {
+        self.difficulty.preset().map_or(self.grid_size, |(size, _)| size)
+    }
+
+    pub fn mines(&self) -> u32 {
+        self.difficulty.preset().map_or(self.mines, |(_, mines)| mines)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        // settings the solver can't work with, after the command line is merged in
+        let [w, h] = self.grid_size();
+        if w == 0 || h == 0 {
+            return Err(format!("board size {w}x{h} needs at least one cell each way"));
+        }
+        if self.cell_size == 0 {
+            return Err(String::from("cell_size can't be 0"));
+        }
+        if self.mines() as u64 >= w as u64 * h as u64 {
+            return Err(format!("{} mines don't fit on a {w}x{h} board with a cell to spare", self.mines()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    pub profile: Option<String>, // profile used when none is given on the command line
+    pub profiles: HashMap<String, Profile>,
+    pub skins: HashMap<String, SkinConfig>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        // a missing config file is fine, everything has defaults
+        if !path.exists() {
+            return Ok(ConfigFile::default());
+        }
+        let text = fs::read_to_string(path)?;
+        let config = toml::from_str(&text)
+            .map_err(|error| format!("failed to parse {}: {error}", path.display()))?;
+        Ok(config)
+    }
+}
+
+/// Final settings used by the solver, after merging the config file and CLI
+pub struct Settings {
+    pub profile_name: String,
+    pub profile: Profile,
+    pub skin: Skin,
+}
+
+impl Settings {
+    pub fn load(cli: &Cli) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = ConfigFile::load(&cli.config)?;
+
+        let profile_name = cli.profile.clone()
+            .or(config.profile.clone())
+            .unwrap_or(String::from("default"));
+        let mut profile = match config.profiles.get(&profile_name) {
+            Some(profile) => {profile.clone()}
+            None if profile_name == "default" => {Profile::default()}
+            None => {
+                return Err(format!("unknown profile '{profile_name}' in {}", cli.config.display()).into());
+            }
+        };
+
+        // command line overrides the profile
+        if let Some(difficulty) = cli.difficulty {
+            profile.difficulty = difficulty;
+        }
+        if cli.width.is_some() || cli.height.is_some() || cli.mines.is_some() {
+            // giving a size or mine count implies a custom board
+            let [w, h] = profile.grid_size();
+            let mines = profile.mines();
+            profile.difficulty = Difficulty::Custom;
+            profile.grid_size = [cli.width.unwrap_or(w), cli.height.unwrap_or(h)];
+            profile.mines = cli.mines.unwrap_or(mines);
+        }
+        if let Some(step_limit) = cli.step_limit {
+            profile.step_limit = step_limit;
+        }
+        if let Some(wait_time) = cli.wait_time {
+            profile.wait_time = wait_time;
+        }
+        if let Some(click_delay) = cli.click_delay {
+            profile.click_delay = click_delay;
+        }
+        if let Some(countdown) = cli.countdown {
+            profile.countdown = countdown;
+        }
+        if let Some(games) = cli.games {
+            profile.games = games;
+        }
+        if let Some(results) = &cli.results {
+            profile.results_log = results.clone();
+        }
+        if let Some(decisions) = &cli.decisions {
+            profile.decision_log = decisions.clone();
+        }
+        if cli.screenshots {
+            profile.save_screenshots = true;
+        }
+        if cli.no_screenshots {
+            profile.save_screenshots = false;
+        }
+        if cli.full_capture {
+            profile.incremental_capture = false;
+        }
+        if cli.quiet {
+            profile.display = DisplayMode::Quiet;
+        }
+        if cli.verbose {
+            profile.display = DisplayMode::Verbose;
+        }
+        profile.check().map_err(|problem| format!("invalid profile '{profile_name}': {problem}"))?;
+
+        let skin_config = match config.skins.get(&profile.skin) {
+            Some(skin) => {skin.clone()}
+            None if profile.skin == "default" => {SkinConfig::default()}
+            None => {
+                return Err(format!("unknown skin '{}' in profile '{profile_name}'", profile.skin).into());
+            }
+        };
+        let template_dir = if skin_config.templates.is_empty() {
+            PathBuf::from("templates").join(&profile.skin)
+        } else {
+            PathBuf::from(&skin_config.templates)
+        };
+        let classifier = match skin_config.classifier {
+            ClassifierKind::Pixel => {Classifier::Pixel}
+            ClassifierKind::Template => {
+                let templates = TemplateSet::load(&template_dir, skin_config.match_threshold)?;
+                if templates.is_empty() && !cli.label {
+                    return Err(format!(
+                        "no templates in {} for skin '{}', run with --label first",
+                        template_dir.display(),
+                        profile.skin,
+                    ).into());
+                }
+                Classifier::Template(templates)
+            }
+        };
+        let skin = Skin {
+            name: profile.skin.clone(),
+            chord: skin_config.chord,
+            chord_min_saved: skin_config.chord_min_saved,
+            classifier,
+            template_dir,
+            match_threshold: skin_config.match_threshold,
+        };
+
+        Ok(Settings {
+            profile_name,
+            profile,
+            skin,
+        })
+    }
+
+    pub fn list_profiles(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+        let config = ConfigFile::load(&cli.config)?;
+        let mut names: Vec<&String> = config.profiles.keys().collect();
+        names.sort();
+        if names.is_empty() {
+            println!("no profiles in {}, using built-in defaults", cli.config.display());
+        }
+        for name in names {
+            let profile = &config.profiles[name];
+            let [w, h] = profile.grid_size();
+            let default = if config.profile.as_ref() == Some(name) {" (default)"} else {""};
+            println!("{name}{default}: {w}x{h}, {} mines, skin '{}'", profile.mines(), profile.skin);
+        }
+        Ok(())
+    }
+}
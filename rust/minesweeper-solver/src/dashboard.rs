@@ -0,0 +1,298 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+use crate::{safety, state_to_color, wait, Board, BoardState, State};
+
+// whether the terminal is in raw/alternate screen mode and needs restoring on exit
+pub static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+const LOG_LENGTH: usize = 500; // max decision log entries kept
+
+pub fn restore() {
+    // leaves the dashboard so normal printing works again
+    if ACTIVE.swap(false, Ordering::Relaxed) {
+        ratatui::restore();
+    }
+}
+
+fn hex_to_color(color: &str) -> Color {
+    let h = color.trim_start_matches('#');
+    let r = u8::from_str_radix(&h[0..2], 16).unwrap_or(0);
+    let g = u8::from_str_radix(&h[2..4], 16).unwrap_or(0);
+    let b = u8::from_str_radix(&h[4..6], 16).unwrap_or(0);
+    Color::Rgb(r, g, b)
+}
+
+struct LogEntry {
+    step: u32,
+    action: String,
+    reason: String,
+}
+
+pub struct Dashboard {
+    terminal: DefaultTerminal,
+    log: VecDeque<LogEntry>, // decision log, newest last
+    action: String, // what the solver is doing right now
+    reason: String, // why it's doing it
+    game: u32, // current game number in the session
+    games: u32, // games in the session
+    session: String, // session statistics summary
+    game_start: Instant,
+}
+
+impl Dashboard {
+    pub fn new() -> io::Result<Self> {
+        let terminal = ratatui::try_init()?;
+        ACTIVE.store(true, Ordering::Relaxed);
+        Ok(Dashboard {
+            terminal,
+            log: VecDeque::new(),
+            action: String::new(),
+            reason: String::new(),
+            game: 0,
+            games: 0,
+            session: String::new(),
+            game_start: Instant::now(),
+        })
+    }
+
+    pub fn start_game(&mut self, game: u32, games: u32) {
+        self.game = game;
+        self.games = games;
+        self.game_start = Instant::now();
+        self.message(0, &format!("Game {game}/{games}"));
+    }
+
+    pub fn set_session(&mut self, summary: String) {
+        self.session = summary;
+    }
+
+    pub fn message(&mut self, step: u32, text: &str) {
+        // adds a line to the decision log without changing the current action
+        self.push_log(step, text, "");
+    }
+
+    fn push_log(&mut self, step: u32, action: &str, reason: &str) {
+        self.log.push_back(LogEntry {
+            step,
+            action: action.to_string(),
+            reason: reason.to_string(),
+        });
+        while self.log.len() > LOG_LENGTH {
+            self.log.pop_front();
+        }
+    }
+
+    pub fn update(&mut self, board: &Board, action: &str, reason: &str) {
+        self.action = action.to_string();
+        self.reason = reason.to_string();
+        if !reason.is_empty() {
+            // only actual decisions go in the log, not capture/update progress
+            self.push_log(board.steps, action, reason);
+        }
+        self.draw(board);
+    }
+
+    pub fn hold(&mut self, board: &Board) {
+        // keeps drawing while paused, until resumed or a single step is let through
+        while safety::is_paused() {
+            if safety::STEP.swap(false, Ordering::Relaxed) {
+                break;
+            }
+            self.draw(board);
+            wait(50);
+        }
+    }
+
+    fn draw(&mut self, board: &Board) {
+        let log = &self.log;
+        let action = &self.action;
+        let reason = &self.reason;
+        let stats = self.stats_content(board);
+        let _ = self.terminal.draw(|frame| {
+            let area = frame.area();
+            let [body, footer] = Layout::vertical([
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ]).areas(area);
+
+            let board_width = (board.grid_size[0] * 2 + 2) as u16;
+            let board_height = (board.grid_size[1] + 3) as u16;
+            let [block_left, block_right] = Layout::horizontal([
+                Constraint::Length(board_width.max(30)),
+                Constraint::Min(1),
+            ]).areas(body);
+
+            let [block_board, block_stats] = Layout::vertical([
+                Constraint::Length(board_height),
+                Constraint::Min(1),
+            ]).areas(block_left);
+
+            let [block_action, block_log] = Layout::vertical([
+                Constraint::Length(4),
+                Constraint::Min(1),
+            ]).areas(block_right);
+
+            frame.render_widget(
+                Paragraph::new(board_content(board))
+                    .block(default_block(" Board ")),
+                block_board,
+            );
+            frame.render_widget(
+                Paragraph::new(stats)
+                    .block(default_block(" Stats ")),
+                block_stats,
+            );
+            frame.render_widget(
+                Paragraph::new(Text::from(vec![
+                    Line::from(Span::styled(action.as_str(), Style::default().fg(Color::Yellow))),
+                    Line::from(Span::styled(reason.as_str(), Style::default().fg(Color::Gray))),
+                ]))
+                    .wrap(Wrap { trim: true })
+                    .block(default_block(" Action ")),
+                block_action,
+            );
+            frame.render_widget(
+                Paragraph::new(log_content(log, block_log.height.saturating_sub(2) as usize))
+                    .block(default_block(" Decision log ")),
+                block_log,
+            );
+
+            let mut status = if safety::is_paused() {
+                vec![
+                    Span::styled(" PAUSED ", Style::default().fg(Color::Black).bg(Color::Yellow)),
+                    Span::styled(format!(" {}", safety::pause_reason()), Style::default().fg(Color::Yellow)),
+                ]
+            } else {
+                vec![Span::styled(" RUNNING ", Style::default().fg(Color::Black).bg(Color::Green))]
+            };
+            status.push(Span::raw("  Q quit | Ctrl+Alt+P pause/resume | Ctrl+Alt+N single step | mouse to a corner: stop"));
+            frame.render_widget(Paragraph::new(Line::from(status)), footer);
+        });
+    }
+
+    fn stats_content(&self, board: &Board) -> Text<'static> {
+        let mut closed = 0;
+        let mut solved = 0;
+        for cell in board.cells.values() {
+            if cell.state == State::Closed {
+                closed += 1;
+            }
+            if cell.solved {
+                solved += 1;
+            }
+        }
+        let total = board.cells.len().max(1);
+
+        let stat = |name: &str, value: String| {
+            Line::from(vec![
+                Span::styled(format!("{name}: "), Style::default().fg(Color::White)),
+                Span::styled(value, Style::default().fg(Color::Yellow)),
+            ])
+        };
+        let mut lines = vec![
+            stat("game", format!("{}/{}", self.game, self.games)),
+            stat("time", format!("{:.1}s", self.game_start.elapsed().as_secs_f64())),
+            stat("steps", board.steps.to_string()),
+            stat("guesses", board.guesses.to_string()),
+            stat("mines left", format!("{}/{}", board.mines_left, board.mines)),
+            stat("closed cells", closed.to_string()),
+            stat("solved", format!("{solved}/{total} ({:.0}%)", solved as f64 / total as f64 * 100.0)),
+            stat("skin", format!("{} (chord {:?})", board.skin.name, board.skin.chord)),
+            stat("last capture", board.capture_stats.last()),
+            stat("captures", board.capture_stats.summary()),
+        ];
+        if !self.session.is_empty() {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(self.session.clone(), Style::default().fg(Color::LightCyan))));
+        }
+        Text::from(lines)
+    }
+}
+
+fn default_block(title: &str) -> Block<'_> {
+    Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_alignment(Alignment::Center)
+}
+
+fn board_content(board: &Board) -> Text<'static> {
+    // mirrors the board, solved cells get a lighter background
+    let w = board.grid_size[0];
+    let h = board.grid_size[1];
+    let mut lines = Vec::new();
+
+    let (face, face_color) = match board.state {
+        BoardState::Unsolved => {(">_<", "#f3ff82")}
+        BoardState::Solved => {(">w<", "#7df084")}
+        BoardState::Failed => {("o_O", "#ff6e6e")}
+    };
+    let spaces = " ".repeat(((w * 2).saturating_sub(1 + 9) / 2) as usize);
+    lines.push(Line::from(vec![
+        Span::raw(format!("{:03}{spaces}", board.mines_left)),
+        Span::styled(face, Style::default().fg(hex_to_color(face_color))),
+        Span::raw(format!("{spaces}{:03}", board.steps)),
+    ]));
+
+    for y in 1..h+1 {
+        let mut row = Vec::new();
+        for x in 1..w+1 {
+            let state = board.get_cell_state(x, y).unwrap_or(State::Mine);
+            let value = board.get_cell_value(x, y).unwrap_or(0);
+            let solved = board.get_cell_solved(x, y).unwrap_or(false);
+            let color = state_to_color(state, Some(value));
+
+            let cell = match state {
+                State::Closed => {String::from("~")}
+                State::Mine => {String::from("X")}
+                State::Flagged => {String::from("!")}
+                State::Open => {
+                    if value > 0 && value < 9 {
+                        value.to_string()
+                    } else {
+                        String::from(" ")
+                    }
+                }
+            };
+            let background = if solved {"#1e1f29"} else {"#0d0e14"};
+            row.push(Span::styled(
+                cell + " ",
+                Style::default().fg(hex_to_color(&color)).bg(hex_to_color(background)),
+            ));
+        }
+        lines.push(Line::from(row));
+    }
+    Text::from(lines)
+}
+
+fn log_content(log: &VecDeque<LogEntry>, height: usize) -> Text<'static> {
+    // newest entries at the bottom, scrolls as new ones come in
+    let skip = log.len().saturating_sub(height);
+    let lines: Vec<Line> = log.iter().skip(skip).map(|entry| {
+        let mut spans = vec![
+            Span::styled(format!("{:>4} ", entry.step), Style::default().fg(Color::DarkGray)),
+            Span::styled(entry.action.clone(), Style::default().fg(Color::White)),
+        ];
+        if !entry.reason.is_empty() {
+            spans.push(Span::styled(
+                format!(" - {}", entry.reason),
+                Style::default().fg(Color::Gray).add_modifier(Modifier::ITALIC),
+            ));
+        }
+        Line::from(spans)
+    }).collect();
+    Text::from(lines)
+}
@@ -0,0 +1,236 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{color_background, color_text, session::GameOutcome, Board, State};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Open,
+    Flag,
+    Chord,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    Start, // safe starting cell shown by the game (no guess mode)
+    Trivial, // a single number is fully satisfied by its flags/closed neighbours
+    Probability, // no logical move, guessed based on the mine probability
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Decision {
+    pub step: u32,
+    pub action: Action,
+    pub cell: [u32; 2], // cell the action was taken on
+    pub rule: Rule,
+    pub cells: Vec<[u32; 2]>, // cells the rule looked at, the source number first
+    pub probability: f64, // chance that `cell` is a mine
+    pub board: Vec<String>, // board before the action, one string per row
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameDecisions {
+    pub session: u64, // when the session started, in unix seconds
+    pub game: u32,
+    pub outcome: GameOutcome,
+    pub decisions: Vec<Decision>,
+}
+
+fn cell_char(state: State, value: u8) -> char {
+    match state {
+        State::Closed => {'~'}
+        State::Flagged => {'!'}
+        State::Mine => {'X'}
+        State::Open => {
+            if value > 0 && value < 9 {
+                (b'0' + value) as char
+            } else {
+                '.'
+            }
+        }
+    }
+}
+
+fn char_cell(c: char) -> (State, u8) {
+    match c {
+        '~' => {(State::Closed, 0)}
+        '!' => {(State::Flagged, 0)}
+        'X' => {(State::Mine, 0)}
+        '1'..='8' => {(State::Open, c as u8 - b'0')}
+        _ => {(State::Open, 0)}
+    }
+}
+
+pub fn snapshot(board: &Board) -> Vec<String> {
+    // compact text copy of the board, so a decision can be checked without the game
+    let mut rows = Vec::new();
+    for y in 1..board.grid_size[1] + 1 {
+        let mut row = String::new();
+        for x in 1..board.grid_size[0] + 1 {
+            let (state, value) = board.get_cell_data(x, y).unwrap_or((State::Closed, 0));
+            row.push(cell_char(state, value));
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+/// Writes every game's decisions to a json file, rewritten after each game
+/// Games from earlier sessions are read back first so they're kept
+pub struct DecisionLog {
+    path: PathBuf,
+    games: Vec<GameDecisions>,
+}
+
+impl DecisionLog {
+    pub fn new(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let games = match fs::read_to_string(path) {
+            Ok(text) if !text.trim().is_empty() => {
+                // don't overwrite a log that can't be read
+                serde_json::from_str(&text).map_err(|error| format!("can't read {}: {error}", path.display()))?
+            }
+            _ => {Vec::new()}
+        };
+        Ok(DecisionLog {
+            path: path.to_path_buf(),
+            games,
+        })
+    }
+
+    pub fn write(&mut self, game: GameDecisions) -> Result<(), Box<dyn std::error::Error>> {
+        self.games.push(game);
+        let file = File::create(&self.path)?;
+        serde_json::to_writer_pretty(file, &self.games)?;
+        Ok(())
+    }
+}
+
+fn cell_at(board: &[String], x: u32, y: u32) -> Option<(State, u8)> {
+    if x == 0 || y == 0 {
+        return None;
+    }
+    let row = board.get(y as usize - 1)?;
+    let c = row.chars().nth(x as usize - 1)?;
+    Some(char_cell(c))
+}
+
+fn check_decision(decision: &Decision) -> Option<String> {
+    // re-checks a trivial decision against the board it was made on
+    // returns what's wrong with it, if anything
+    if decision.rule != Rule::Trivial {
+        return None;
+    }
+    let [sx, sy] = *decision.cells.first()?;
+    let (state, value) = cell_at(&decision.board, sx, sy)?;
+    if state != State::Open || value == 0 {
+        return Some(format!("source ({sx},{sy}) isn't an open number"));
+    }
+
+    let mut closed = 0;
+    let mut flagged = 0;
+    for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+        let x = sx as i64 + dx;
+        let y = sy as i64 + dy;
+        if x < 1 || y < 1 {
+            continue;
+        }
+        match cell_at(&decision.board, x as u32, y as u32) {
+            Some((State::Closed, _)) => {closed += 1}
+            Some((State::Flagged, _)) => {flagged += 1}
+            _ => {}
+        }
+    }
+
+    match decision.action {
+        Action::Open | Action::Chord => {
+            if flagged != value {
+                return Some(format!("({sx},{sy}) = {value} but {flagged} flags around it"));
+            }
+        }
+        Action::Flag => {
+            if closed + flagged != value {
+                return Some(format!("({sx},{sy}) = {value} but {} closed + flagged around it", closed + flagged));
+            }
+        }
+    }
+    None
+}
+
+fn print_board(decision: &Decision) {
+    // acted on cell in red, other involved cells in yellow
+    for (y, row) in decision.board.iter().enumerate() {
+        let mut line = String::new();
+        for (x, c) in row.chars().enumerate() {
+            let pos = [x as u32 + 1, y as u32 + 1];
+            let text = format!("{c} ");
+            if pos == decision.cell {
+                line += &color_background(&text, "#a33a3a");
+            } else if decision.cells.contains(&pos) {
+                line += &color_background(&text, "#7a6a1e");
+            } else {
+                line += &text;
+            }
+        }
+        println!("{line}");
+    }
+}
+
+pub fn replay(path: &Path, game: Option<u32>, show_boards: bool) -> Result<(), Box<dyn std::error::Error>> {
+    // offline replay of a decision log, no screen capture or mouse input
+    let text = fs::read_to_string(path)?;
+    let games: Vec<GameDecisions> = serde_json::from_str(&text)?;
+
+    // default to the last lost game, since that's usually the one worth looking at
+    // game numbers start over each session, the latest one with the number is picked
+    let selected = match game {
+        Some(number) => {games.iter().rev().find(|g| g.game == number)}
+        None => {games.iter().rev().find(|g| g.outcome == GameOutcome::Lost).or(games.last())}
+    };
+    let Some(selected) = selected else {
+        return Err(format!("no matching game in {}", path.display()).into());
+    };
+
+    println!("game {} ({:?}), {} decisions", selected.game, selected.outcome, selected.decisions.len());
+    let mut problems = 0;
+    for decision in &selected.decisions {
+        let [x, y] = decision.cell;
+        let line = format!(
+            "{:>4} {:?} ({x},{y}) by {:?}, mine chance {:.0}%, cells {:?}",
+            decision.step,
+            decision.action,
+            decision.rule,
+            decision.probability * 100.0,
+            decision.cells,
+        );
+        match check_decision(decision) {
+            Some(problem) => {
+                problems += 1;
+                println!("{}", color_text(&format!("{line}\n     ^ bad decision: {problem}"), "#ff6e6e"));
+            }
+            None => {println!("{line}")}
+        }
+        if show_boards {
+            print_board(decision);
+            println!();
+        }
+    }
+
+    // the last decision of a lost game is the one that hit the mine
+    if selected.outcome == GameOutcome::Lost
+        && let Some(last) = selected.decisions.last() {
+        println!();
+        println!(
+            "lost at step {} on {:?} ({},{}) by {:?} ({:.0}% mine chance)",
+            last.step, last.action, last.cell[0], last.cell[1], last.rule, last.probability * 100.0,
+        );
+        print_board(last);
+    }
+    println!("\n{problems} decisions failed the re-check");
+    Ok(())
+}
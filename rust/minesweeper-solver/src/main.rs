@@ -1,15 +1,34 @@
 #![allow(unused)]
 
+mod capture;
+mod config;
+mod dashboard;
+mod decisions;
+mod recognition;
+mod safety;
+mod session;
+
+use clap::Parser;
+use capture::{CaptureStats, CellArea};
+use config::{Cli, DisplayMode, Profile, Settings};
+use dashboard::Dashboard;
+use decisions::{Action, Decision, DecisionLog, GameDecisions, Rule};
+use recognition::{CellReading, Classifier};
+use session::{GameOutcome, GameResult, ResultLog, SessionStats};
+use serde::Deserialize;
 use fs_extra::dir;
 use core::time;
 use std::{array, iter::FlatMap, os, process::exit, time::{Duration, Instant}};
 use xcap::Monitor;
-use rustautogui::RustAutoGui;
+use rustautogui::{MouseClick, RustAutoGui};
 use std::collections::HashMap;
 use rdev::{Event, listen, EventType, Key};
 use std::thread;
 use std::io::{self, Write};
-use rand::Rng;
+use rand::{Rng, rngs::ThreadRng};
+use std::path::{Path, PathBuf};
+use std::cell::RefCell;
+use image::RgbaImage;
 
 /*  COLORS (for display)
 closed = 4C545C (DCDCDC)
@@ -34,6 +53,13 @@ fn wait(millis: u64) {
     thread::sleep(Duration::from_millis(millis));
 }
 
+fn quit(message: &str) -> ! {
+    // leaves the dashboard first so the message is actually visible
+    dashboard::restore();
+    println!("{message}");
+    exit(0);
+}
+
 fn rgb_to_hex(r: u8, g: u8, b: u8) -> String {
     format!("{r:02X}{g:02X}{b:02X}")
 }
@@ -114,6 +140,45 @@ enum State {
     Mine,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ChordMode {
+    Off, // never chord, open each neighbour with a left click
+    Middle, // middle click on the number
+    LeftRight, // left + right click at the same time on the number
+}
+
+struct Skin {
+    name: String,
+    chord: ChordMode, // how this skin/site wants chords to be clicked
+    chord_min_saved: u32, // only chord if it saves at least this many clicks
+    classifier: Classifier, // how cells are read from a screenshot
+    template_dir: PathBuf, // labelled cell templates for the template classifier
+    match_threshold: f64, // minimum template correlation to accept a match
+}
+
+impl Skin {
+    fn default_skin() -> Self {
+        Skin {
+            name: String::from("default"),
+            chord: ChordMode::Middle,
+            chord_min_saved: 1,
+            classifier: Classifier::Pixel,
+            template_dir: PathBuf::from("templates/default"),
+            match_threshold: 0.8,
+        }
+    }
+
+    fn should_chord(&self, closed: usize) -> bool {
+        // a chord replaces one left click per closed neighbour with a single click
+        if self.chord == ChordMode::Off || closed == 0 {
+            return false;
+        }
+        let saved = closed as u32 - 1;
+        saved >= self.chord_min_saved
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum BoardState {
     Unsolved, // currently solving
@@ -133,8 +198,16 @@ struct Board {
     rag: RustAutoGui,
     monitor: Monitor,
     steps: u32, // current steps/actions taken
+    guesses: u32, // random clicks taken this game
+    decisions: Vec<Decision>, // every action taken this game and why
     state: BoardState, // solved, in progress, or failed
-    draw: bool, // whether to draw the board/status messages
+    dashboard: Option<RefCell<Dashboard>>, // live view of the board, None when quiet
+    skin: Skin, // per skin settings (chording etc.)
+    click_delay: u64, // ms to wait after each click
+    face: [u32; 2], // reset face button, in screen px
+    mouse_tolerance: u32, // px the mouse can drift before it counts as the user moving it
+    dirty: RefCell<Option<CellArea>>, // cells touched since the last capture
+    capture_stats: CaptureStats, // capture timings for the whole session
 }
 
 impl Board {
@@ -155,6 +228,108 @@ impl Board {
         }
     }
 
+    fn reset(&mut self) {
+        // clears everything for a new game
+        self.cells.clear();
+        self.initialize_board();
+        self.mines_left = self.mines;
+        self.steps = 0;
+        self.guesses = 0;
+        self.decisions.clear();
+        self.state = BoardState::Unsolved;
+        self.mark_dirty(capture::full_area(self));
+    }
+
+    fn mark_dirty(&self, area: CellArea) {
+        // grows the area that needs capturing on the next update
+        let mut dirty = self.dirty.borrow_mut();
+        *dirty = Some(capture::merge(*dirty, area));
+    }
+
+    fn take_dirty(&self) -> Option<CellArea> {
+        self.dirty.borrow_mut().take()
+    }
+
+    fn move_mouse(&self, x: u32, y: u32) -> Result<(), String> {
+        // checks the safety interlocks before taking control of the mouse
+        if let Ok((mouse_x, mouse_y)) = self.rag.get_mouse_position() {
+            if safety::in_corner(mouse_x as f64, mouse_y as f64) {
+                quit("Fail-safe: mouse moved to a screen corner, exiting...");
+            }
+            if safety::user_moved(mouse_x, mouse_y, self.mouse_tolerance) {
+                safety::pause("mouse moved by user");
+            }
+        }
+        self.hold();
+
+        let res = self.rag.move_mouse_to_pos(x, y, 0.0).map_err(|error| error.to_string());
+        if res.is_ok() {
+            safety::set_expected(x, y);
+        }
+        res
+    }
+
+    fn hold(&self) {
+        // blocks while the solver is paused, a single step runs on until the next decision
+        if !safety::is_held() {
+            return;
+        }
+        match &self.dashboard {
+            Some(dashboard) => {dashboard.borrow_mut().hold(self);}
+            None => {
+                println!("paused: {} (Ctrl+Alt+P to resume, Ctrl+Alt+N to step)", safety::pause_reason());
+                while safety::is_paused() && !safety::STEP.swap(false, std::sync::atomic::Ordering::Relaxed) {
+                    wait(50);
+                }
+            }
+        };
+        if safety::is_paused() {
+            // let through by a single step
+            safety::start_step();
+        }
+        safety::clear_expected();
+        // anything could have changed while paused
+        self.mark_dirty(capture::full_area(self));
+    }
+
+    fn click_face(&self) {
+        // clicks the reset face to start a new game
+        let res = self.move_mouse(self.face[0], self.face[1]);
+        match res {
+            Ok(value) => {
+                self.rag.left_click();
+                self.mark_dirty(capture::full_area(self));
+                wait(self.click_delay);
+            }
+            Err(error) => {
+                quit(&format!("move mouse error: {error}"));
+            }
+        }
+    }
+
+    fn record(&mut self, action: Action, cell: [u32; 2], rule: Rule, cells: Vec<[u32; 2]>, probability: f64) {
+        // saves a decision with a copy of the board it was made on
+        let decision = Decision {
+            step: self.steps,
+            action,
+            cell,
+            rule,
+            cells,
+            probability,
+            board: decisions::snapshot(self),
+        };
+        self.decisions.push(decision);
+    }
+
+    fn mine_chance(&self) -> f64 {
+        // chance that a random closed cell is a mine
+        let closed = self.cells.values().filter(|cell| cell.state == State::Closed).count();
+        if closed == 0 {
+            return 0.0;
+        }
+        self.mines_left as f64 / closed as f64
+    }
+
     fn get_cell_position(&self, x: u32, y: u32) -> Option<[u32; 2]> {
         if x > self.grid_size[0] || y > self.grid_size[1] {
             return None;
@@ -249,30 +424,63 @@ impl Board {
     fn open_cell(&self, x: u32, y: u32) {
         let position = self.get_cell_position(x, y);
         if let Some(position) = position {
-            let res = self.rag.move_mouse_to_pos(position[0], position[1], 0.0);
+            let res = self.move_mouse(position[0], position[1]);
             match res {
                 Ok(value) => {
                     self.rag.left_click();
-                    wait(50);
+                    self.mark_dirty(capture::around(self, x, y, 0));
+                    wait(self.click_delay);
                 }
                 Err(error) => {
-                    println!("move mouse error: {error}");
-                    exit(0);
+                    quit(&format!("move mouse error: {error}"));
                 }
             }
 
         }
     }
 
+    fn chord_cell(&self, x: u32, y: u32) {
+        // clicks a number whose flags are satisfied, opening all of its closed neighbours
+        let position = self.get_cell_position(x, y);
+        if let Some(position) = position {
+            let res = self.move_mouse(position[0], position[1]);
+            match res {
+                Ok(value) => {
+                    match self.skin.chord {
+                        ChordMode::Middle => {
+                            self.rag.middle_click();
+                        }
+                        ChordMode::LeftRight => {
+                            self.rag.click_down(MouseClick::LEFT);
+                            self.rag.click_down(MouseClick::RIGHT);
+                            wait(10);
+                            self.rag.click_up(MouseClick::LEFT);
+                            self.rag.click_up(MouseClick::RIGHT);
+                        }
+                        ChordMode::Off => {}
+                    }
+                    self.mark_dirty(capture::around(self, x, y, 1));
+                    wait(self.click_delay);
+                }
+                Err(error) => {
+                    quit(&format!("move mouse error: {error}"));
+                }
+            }
+        }
+    }
+
     fn flag_cell(&mut self, x: u32, y: u32) {
         let position = self.get_cell_position(x, y);
         if let Some(position) = position {
             self.set_cell_state(x, y, State::Flagged);
             self.set_cell_solved(x, y, true);
             self.mines_left -= 1;
-            self.rag.move_mouse_to_pos(position[0], position[1], 0.0);
+            if let Err(error) = self.move_mouse(position[0], position[1]) {
+                quit(&format!("move mouse error: {error}"));
+            }
             self.rag.right_click();
-            wait(50);
+            self.mark_dirty(capture::around(self, x, y, 0));
+            wait(self.click_delay);
         }
     }
 
@@ -300,70 +508,24 @@ impl Board {
         cells
     }
 
-    fn display_board(&self, status: &str) {
-        if !self.draw {
-            return;
+    fn display_board(&self, action: &str, reason: &str) {
+        // shows the current board state and what the solver is doing on the dashboard
+        if let Some(dashboard) = &self.dashboard {
+            dashboard.borrow_mut().update(self, action, reason);
         }
-        // prints out the current minesweeper board state
-        let w = self.grid_size[0];
-        let h = self.grid_size[1];
-
-        print!("\x1B[{}A", 3+h);
-        Write::flush(&mut io::stdout()).unwrap();
-
-        let mut board_text = String::new();
-
-        let mines = format!("{:03}", self.mines_left);
-        let spaces = " ".repeat(((w * 2 - 1 - 9) / 2) as usize); // spaces in between
-
-        let face = match self.state {
-            BoardState::Unsolved => {color_text(">_<", "#f3ff82")}
-            BoardState::Solved => {color_text(">w<", "#7df084")}
-            BoardState::Failed => {color_text("o_O", "#ff6e6e")}
-        };
-        let steps = format!("{:03}", self.steps);
-        let top = mines + &spaces + &face + &spaces + &steps + "\n";
-        board_text.push_str(&top);
-
-        for y in 1..h+1 {
-            let mut row = String::new();
-            for x in 1..w+1 {
-                let state = self.get_cell_state(x, y).unwrap_or(State::Mine);
-                let value = self.get_cell_value(x, y).unwrap_or(0);
-                let solved = self.get_cell_solved(x, y).unwrap_or(false);
-                let color = &state_to_color(state, Some(value));
-
-                let cell = match state {
-                    State::Closed => {"~"}
-                    State::Mine => {"X"}
-                    State::Flagged => {"!"}
-                    State::Open => {
-                        if value > 0 && value < 9 {
-                            &value.to_string()
-                        } else {
-                            " "
-                        }
-                    }
-                };
-                let text = if !solved {
-                    // &color_text(cell, color)
-                    &color_background(&color_text(&(cell.to_string() + " "), color), "#0d0e14")
-                } else {
-                    &color_background(&color_text(&(cell.to_string() + " "), color), "#1e1f29")
-                };
-                row = row + text;
-            }
-
-            row += "\n";
-            board_text.push_str(&row);
+        if !reason.is_empty() {
+            // only decisions have a reason, a single step runs one of them
+            safety::end_step();
+            self.hold();
         }
+    }
 
-        // print!("\x1b[?1049h"); // switch to alternate screen
-        // wait(1000);
-        println!("{board_text}");
-        println!("{status:<60}");
-        // wait(1000);
-        // print!("\x1b[?1049l");
+    fn log_message(&self, text: &str) {
+        // adds a line to the dashboard log, or prints it when there is no dashboard
+        match &self.dashboard {
+            Some(dashboard) => {dashboard.borrow_mut().message(self.steps, text)}
+            None => {println!("{text}")}
+        }
     }
 }
 
@@ -379,70 +541,22 @@ impl Cell {
 
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // seperate thread to listen for "Q" to quit immediately
-    thread::spawn(|| {
-        if let Err(error) = listen(move |event| {
-            if let EventType::KeyPress(Key::KeyQ) = event.event_type {
-                println!("Exiting...");
-                exit(0);
-            }
-        }) {
-            println!("Error: {error:?}")
-        }
-    });
-
-    for i in (1..=2).rev() {
-        print!("\rstarting in {i}...");
-        Write::flush(&mut io::stdout()).unwrap();
-        wait(1000);
-    }
-    println!("\r                 ");
-
-    let start = Instant::now();
-    let mut rag = RustAutoGui::new(false)?;
-    let mut rng = rand::rng();
-
-    let monitors = Monitor::all()?;
-    dir::create_all("screenshots", true)?;
-    let monitor = monitors
-        .into_iter()
-        .find(|m| m.is_primary().unwrap_or(false))
-        .expect("No primary monitor found");
-
-    let mut board = Board {
-        // corners: [980, 251, 1400, 774],
-        corners: [1173, 308, 2433, 1111],
-        // inner_board_corner: [1010, 386],
-        inner_board_corner: [1203, 443],
-        cell_size: 40,
-        grid_size: [30, 16],
-        mines: 50,
-        mines_left: 50,
-        cells: HashMap::new(),
-        rag,
-        monitor,
-        steps: 0,
-        state: BoardState::Unsolved,
-        draw: true,
-    };
-
-    board.initialize_board();
-
+fn play_game(board: &mut Board, profile: &Profile, rng: &mut ThreadRng) -> Result<GameOutcome, Box<dyn std::error::Error>> {
+    let verbose = profile.display == DisplayMode::Verbose;
     let capture_width = board.corners[2] - board.corners[0];
     let capture_height = board.corners[3] - board.corners[1];
+    // full board image, incremental captures are pasted into it
+    let mut image = RgbaImage::new(capture_width, capture_height);
 
     // for looping through cells easier
     let x_range = 1..board.grid_size[0] + 1;
     let y_range = 1..board.grid_size[1] + 1;
 
-    // print!("\x1B[{}B", 3+board.grid_size[1]);
-    print!("{}", String::from("\n").repeat((3+board.grid_size[1]) as usize));
-    board.display_board("Setting up");
+    board.display_board("Setting up", "");
 
     // ################ MAIN LOGIC LOOP ################
-    let mut step_limit = 500;
-    let wait_time = 500;
+    let mut step_limit = profile.step_limit;
+    let wait_time = profile.wait_time;
     let mut is_new = true;
     let mut should_update = true;
     let mut stuck_tries = 0; // loops before clicking random cell
@@ -455,74 +569,96 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         if should_update {
             should_update = false;
-            board.display_board("Capturing screen");
-            wait(wait_time);
-            // ######## get current game state
-            let image = board.monitor.capture_region(
-                board.corners[0],
-                board.corners[1],
-                capture_width,
-                capture_height
-            )?;
-
-            // println!(
-            //     "> captured region {}x{} at ({}, {})",
-            //     image.width(),
-            //     image.height(),
-            //     board.corners[0],
-            //     board.corners[1],
-            // );
-
-            image.save(format!(
-                "screenshots/monitor-{}-region.png",
-                normalized(board.monitor.name()?) 
-            ))?;
-
-            board.display_board("Updating board");
-            wait(wait_time);
-            // ######## update board (if needed)
-            for x in x_range.clone() {
-                for y in y_range.clone() {
-                    if let Some(solved) = board.get_cell_solved(x, y) && solved {
-                        // cell already solved; continue
-                        continue;
-                    }
+            // only capture the cells touched since the last capture
+            let mut area = match board.take_dirty() {
+                Some(area) if profile.incremental_capture => {area}
+                _ => {capture::full_area(board)}
+            };
+            'capture: loop {
+                board.display_board("Capturing screen", "");
+                wait(wait_time);
+                // ######## get current game state
+                let sample = capture::capture(board, &mut image, area)?;
+                board.capture_stats.add(&sample);
+                if verbose {
+                    board.display_board(&format!(
+                        "Captured cells ({},{}) to ({},{}): {}",
+                        area[0], area[1], area[2], area[3],
+                        board.capture_stats.last(),
+                    ), "");
+                }
 
-                    if let Some(state) = board.get_cell_state(x, y) && state != State::Closed {
-                        // cell already opened; we don't need to update it
-                        continue;
-                    }
+                if profile.save_screenshots {
+                    image.save(format!(
+                        "screenshots/monitor-{}-region.png",
+                        normalized(board.monitor.name()?)
+                    ))?;
+                }
 
-                    if let Some(pos) = board.get_cell_position_board(x, y) {
-                        let pixel = image.get_pixel(pos[0], pos[1]);
-                        let color = rgb_to_hex(pixel[0], pixel[1], pixel[2]);
-                        if &color == "66DD66" {
-                            board.display_board(&format!("No guess mode: Clicking first cell ({x}, {y})"));
-                            wait(wait_time);
-                            board.open_cell(x, y);
-                            continue 'main;
+                board.display_board("Updating board", "");
+                wait(wait_time);
+                // ######## update board (if needed)
+                let mut grow = false; // a zero opened on the edge, cells outside the area may have opened too
+                for x in area[0]..area[2] + 1 {
+                    for y in area[1]..area[3] + 1 {
+                        if let Some(solved) = board.get_cell_solved(x, y) && solved {
+                            // cell already solved; continue
+                            continue;
                         }
-                        let data = color_to_state(&color);
-                        if let Some((state, value)) = data {
-                            if is_new && state != State::Closed {
-                                is_new = false;
-                            }
 
-                            if state == State::Open && value == 0 {
-                                board.set_cell_solved(x, y, true);
-                            }
+                        if let Some(state) = board.get_cell_state(x, y) && state != State::Closed {
+                            // cell already opened; we don't need to update it
+                            continue;
+                        }
 
-                            if state == State::Flagged {
-                                board.set_cell_solved(x, y, true);
-                                board.mines_left -= 1;
+                        match board.skin.classifier.classify(board, &image, x, y) {
+                            Ok(CellReading::Start) => {
+                                board.display_board(
+                                    &format!("Clicking first cell ({x}, {y})"),
+                                    "No guess mode marks a safe starting cell",
+                                );
+                                wait(wait_time);
+                                board.record(Action::Open, [x, y], Rule::Start, vec![[x, y]], 0.0);
+                                board.open_cell(x, y);
+                                continue 'main;
+                            }
+                            Ok(CellReading::Known(state, value)) => {
+                                if is_new && state != State::Closed {
+                                    is_new = false;
+                                }
+
+                                if state == State::Open && value == 0 {
+                                    board.set_cell_solved(x, y, true);
+                                    if capture::on_inner_edge(board, area, x, y) {
+                                        grow = true;
+                                    }
+                                }
+
+                                if state == State::Flagged {
+                                    board.set_cell_solved(x, y, true);
+                                    board.mines_left -= 1;
+                                }
+                                board.set_cell(x, y, state, value);
+                            }
+                            Err(problem) => {
+                                // the capture doesn't look like the board (window moved, covered, lost focus...)
+                                safety::pause("board not recognised");
+                                board.display_board(
+                                    "Waiting for the board",
+                                    &format!("cell ({x}, {y}) not recognised: {problem}"),
+                                );
+                                board.hold();
+                                should_update = true;
+                                continue 'main;
                             }
-                            board.set_cell(x, y, state, value);
-                        } else {
-                            println!("unknown color! cell ({}, {}) color={}", x, y, color_text(&color, &color));
-                            exit(0)
                         }
                     }
                 }
+
+                if !grow || capture::is_full(board, area) {
+                    break 'capture;
+                }
+                area = capture::full_area(board);
             }
         }
 
@@ -531,7 +667,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             for y in y_range.clone() {
                 if let Some(state) = board.get_cell_state(x, y) && state == State::Mine {
                     board.state = BoardState::Failed;
-                    board.display_board("Board failed! D:");
+                    board.display_board("Board failed! D:", &format!("Mine revealed at ({x}, {y})"));
                     wait(wait_time);
                     break 'main;
                 }
@@ -540,19 +676,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // ######## if board is new, click random cell
         if is_new {
-            board.display_board("Picking first cell");
+            board.display_board("Picking first cell", "");
             wait(wait_time);
             let rand_x = rng.random_range(1..=board.grid_size[0]);
             let rand_y = rng.random_range(1..=board.grid_size[1]);
-            board.display_board(&format!("Clicking cell ({rand_x}, {rand_y})"));
+            board.display_board(
+                &format!("Clicking cell ({rand_x}, {rand_y})"),
+                "New board, the first click is a random guess",
+            );
             wait(wait_time);
+            let chance = board.mine_chance();
+            board.record(Action::Open, [rand_x, rand_y], Rule::Probability, vec![[rand_x, rand_y]], chance);
             board.open_cell(rand_x, rand_y);
+            board.guesses += 1;
             should_update = true;
             continue 'main;
         }
 
         // ######## check for guaranteed mines or easy safe opens
-        board.display_board("Checking surrounding tiles");
+        board.display_board("Checking surrounding tiles", "");
         wait(wait_time);
         for x in x_range.clone() {
             for y in y_range.clone() {
@@ -576,18 +718,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             flagged.push(cell);
                         }
                     }
+                    // source number first, then the neighbours the rule used
+                    let mut involved = vec![[x, y]];
+                    involved.extend(closed.iter().chain(flagged.iter()).map(|cell| [cell.x, cell.y]));
 
                     if value == flagged.len() as u8 {
                         // all surrounding mines are flagged; open closed cells (if any) or mark as solved
+                        if board.skin.should_chord(closed.len()) {
+                            board.display_board(
+                                &format!("Chording cell ({x},{y}): opening {} cells", closed.len()),
+                                &format!("({x},{y}) = {value} and all {value} mines are flagged"),
+                            );
+                            wait(wait_time);
+                            board.record(Action::Chord, [x, y], Rule::Trivial, involved, 0.0);
+                            board.chord_cell(x, y);
+                            should_update = true;
+                            continue 'main;
+                        }
                         if !closed.is_empty() {
-                            let cell = closed[0];
-                            board.display_board(&format!("Opening cell ({},{}) from ({},{})", cell.x, cell.y, x, y));
+                            let cell = [closed[0].x, closed[0].y];
+                            board.display_board(
+                                &format!("Opening cell ({},{}) from ({},{})", cell[0], cell[1], x, y),
+                                &format!("({x},{y}) = {value} and all {value} mines are flagged"),
+                            );
                             wait(wait_time);
-                            board.open_cell(cell.x, cell.y);
+                            board.record(Action::Open, cell, Rule::Trivial, involved, 0.0);
+                            board.open_cell(cell[0], cell[1]);
                             should_update = true;
                             continue 'main;
                         }
-                        board.display_board(&format!("Marking cell ({x},{y}) solved: All surrounding mines flagged"));
+                        board.display_board(
+                            &format!("Marking cell ({x},{y}) solved"),
+                            "All surrounding mines flagged and no closed cells left",
+                        );
                         wait(wait_time);
                         board.set_cell_solved(x, y, true);
                         continue 'main;
@@ -595,10 +758,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     
                     if value == (flagged.len() + closed.len()) as u8 && !closed.is_empty() {
                         // surrounding unopened tiles match mine count; flag cells
-                        let cell = closed[0];
-                        board.display_board(&format!("Flagging cell ({},{}) from ({},{})", cell.x, cell.y, x, y));
+                        let cell = [closed[0].x, closed[0].y];
+                        board.display_board(
+                            &format!("Flagging cell ({},{}) from ({},{})", cell[0], cell[1], x, y),
+                            &format!("({x},{y}) = {value} and only {} closed + flagged cells around it", closed.len() + flagged.len()),
+                        );
                         wait(wait_time);
-                        board.flag_cell(cell.x, cell.y);
+                        board.record(Action::Flag, cell, Rule::Trivial, involved, 1.0);
+                        board.flag_cell(cell[0], cell[1]);
                         continue 'main;
                     }
                 }
@@ -617,22 +784,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         if board_solved {
             board.state = BoardState::Solved;
-            board.display_board("Board solved! :D");
+            board.display_board("Board solved! :D", "Every cell is solved");
             wait(wait_time);
             break 'main;
         }
 
         if stuck_tries >= 3 {
             // ######## nothing else to do, click random cell
-            board.display_board("I'm stuck! Picking random cell");
+            board.display_board("I'm stuck! Picking random cell", "");
             wait(wait_time);
             'random: loop {
                 let rand_x = rng.random_range(1..=board.grid_size[0]);
                 let rand_y = rng.random_range(1..=board.grid_size[1]);
                 if let Some(state) = board.get_cell_state(rand_x, rand_y) && state == State::Closed {
-                    board.display_board(&format!("Clicking cell ({rand_x}, {rand_y})"));
+                    board.display_board(
+                        &format!("Clicking cell ({rand_x}, {rand_y})"),
+                        "No logical move left, guessing a random closed cell",
+                    );
                     wait(wait_time);
+                    let chance = board.mine_chance();
+                    board.record(Action::Open, [rand_x, rand_y], Rule::Probability, vec![[rand_x, rand_y]], chance);
                     board.open_cell(rand_x, rand_y);
+                    board.guesses += 1;
                     should_update = true;
                     break 'random;
                 }
@@ -643,6 +816,187 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         should_update = true;
     }
 
+    let outcome = match board.state {
+        BoardState::Solved => {GameOutcome::Won}
+        BoardState::Failed => {GameOutcome::Lost}
+        BoardState::Unsolved => {GameOutcome::StepLimit}
+    };
+    Ok(outcome)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    if cli.list_profiles {
+        return Settings::list_profiles(&cli);
+    }
+    if let Some(path) = &cli.replay {
+        return decisions::replay(path, cli.replay_game, cli.replay_boards);
+    }
+    let settings = Settings::load(&cli)?;
+    let profile = &settings.profile;
+    let verbose = profile.display == DisplayMode::Verbose;
+    if verbose {
+        let [w, h] = profile.grid_size();
+        println!(
+            "profile '{}': {w}x{h}, {} mines, skin '{}', chord {:?}",
+            settings.profile_name,
+            profile.mines(),
+            settings.skin.name,
+            settings.skin.chord,
+        );
+    }
+
+    // seperate thread to listen for "Q" to quit immediately, "Ctrl+Alt+P" to pause/resume and "Ctrl+Alt+N" to single step
+    // and to stop when the mouse is moved into a screen corner
+    thread::spawn(|| {
+        // the keys are global, the modifiers keep typing in other windows from pausing the solver
+        let mut ctrl = false;
+        let mut alt = false;
+        if let Err(error) = listen(move |event| {
+            match event.event_type {
+                EventType::KeyPress(Key::ControlLeft | Key::ControlRight) => {ctrl = true}
+                EventType::KeyRelease(Key::ControlLeft | Key::ControlRight) => {ctrl = false}
+                EventType::KeyPress(Key::Alt | Key::AltGr) => {alt = true}
+                EventType::KeyRelease(Key::Alt | Key::AltGr) => {alt = false}
+                EventType::KeyPress(Key::KeyQ) => {quit("Exiting...")}
+                EventType::KeyPress(Key::KeyP) if ctrl && alt => {safety::toggle_pause()}
+                EventType::KeyPress(Key::KeyN) if ctrl && alt => {safety::step()}
+                EventType::MouseMove { x, y } if safety::in_corner(x, y) => {
+                    quit("Fail-safe: mouse moved to a screen corner, exiting...");
+                }
+                _ => {}
+            }
+        }) {
+            println!("Error: {error:?}")
+        }
+    });
+
+    for i in (1..=profile.countdown).rev() {
+        print!("\rstarting in {i}...");
+        Write::flush(&mut io::stdout()).unwrap();
+        wait(1000);
+    }
+    println!("\r                 ");
+
+    let start = Instant::now();
+    let mut rag = RustAutoGui::new(false)?;
+    let mut rng = rand::rng();
+
+    let monitors = Monitor::all()?;
+    if profile.save_screenshots {
+        dir::create_all("screenshots", true)?;
+    }
+    let monitor = monitors
+        .into_iter()
+        .find(|m| m.is_primary().unwrap_or(false))
+        .expect("No primary monitor found");
+    safety::set_screen(monitor.x()?, monitor.y()?, monitor.width()?, monitor.height()?, profile.failsafe_margin);
+
+    let mut board = Board {
+        corners: profile.corners,
+        inner_board_corner: profile.inner_board_corner,
+        cell_size: profile.cell_size,
+        grid_size: profile.grid_size(),
+        mines: profile.mines(),
+        mines_left: profile.mines(),
+        cells: HashMap::new(),
+        rag,
+        monitor,
+        steps: 0,
+        guesses: 0,
+        decisions: Vec::new(),
+        state: BoardState::Unsolved,
+        dashboard: None,
+        skin: settings.skin,
+        click_delay: profile.click_delay,
+        face: profile.face,
+        mouse_tolerance: profile.mouse_tolerance,
+        dirty: RefCell::new(None),
+        capture_stats: CaptureStats::default(),
+    };
+
+    if cli.label {
+        // one capture of the current board, then label its cells in the terminal
+        let image = board.monitor.capture_region(
+            board.corners[0],
+            board.corners[1],
+            board.corners[2] - board.corners[0],
+            board.corners[3] - board.corners[1],
+        )?;
+        return recognition::label(&board, &image, &board.skin.template_dir, board.skin.match_threshold);
+    }
+
+    if profile.display != DisplayMode::Quiet {
+        board.dashboard = Some(RefCell::new(Dashboard::new()?));
+    }
+
+    // ################ SESSION LOOP ################
+    let mut log = if profile.results_log.is_empty() {
+        None
+    } else {
+        Some(ResultLog::new(Path::new(&profile.results_log)))
+    };
+    let mut decision_log = if profile.decision_log.is_empty() {
+        None
+    } else {
+        match DecisionLog::new(Path::new(&profile.decision_log)) {
+            Ok(decision_log) => {Some(decision_log)}
+            Err(error) => {
+                dashboard::restore();
+                return Err(error);
+            }
+        }
+    };
+    let session_start = session::unix_time();
+    let mut stats = SessionStats::default();
+    for game in 1..=profile.games {
+        board.reset();
+        if let Some(dashboard) = &board.dashboard {
+            dashboard.borrow_mut().start_game(game, profile.games);
+        }
+        let game_start = Instant::now();
+        let outcome = match play_game(&mut board, profile, &mut rng) {
+            Ok(outcome) => {outcome}
+            Err(error) => {
+                dashboard::restore();
+                return Err(error);
+            }
+        };
+        let result = GameResult {
+            game,
+            outcome,
+            time: game_start.elapsed().as_secs_f64(),
+            steps: board.steps,
+            guesses: board.guesses,
+        };
+        stats.add(&result);
+        if let Some(log) = &mut log
+            && let Err(error) = log.write(&result) {
+            board.log_message(&format!("failed to write {}: {error}", profile.results_log));
+        }
+        if let Some(decision_log) = &mut decision_log {
+            let game_decisions = GameDecisions {
+                session: session_start,
+                game,
+                outcome,
+                decisions: std::mem::take(&mut board.decisions),
+            };
+            if let Err(error) = decision_log.write(game_decisions) {
+                board.log_message(&format!("failed to write {}: {error}", profile.decision_log));
+            }
+        }
+
+        board.log_message(&session::result_line(&result, profile.games));
+        if let Some(dashboard) = &board.dashboard {
+            dashboard.borrow_mut().set_session(stats.summary());
+        }
+
+        if game < profile.games {
+            board.click_face();
+            wait(profile.restart_delay);
+        }
+    }
+
     // for cell in board.cells.values() {
     //     println!("cell ({}, {}) is {:?}, value: {}", cell.x, cell.y, cell.state, cell.value);
     // }
@@ -713,7 +1067,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // let mouse_pos = board.rag.get_mouse_position()?;
     // println!("mouse position: ({}, {})", mouse_pos.0, mouse_pos.1);
 
-    println!("\nruntime: {:?}\nsteps: {}", start.elapsed(), board.steps);
+    // keep the final board on screen for a moment before leaving the dashboard
+    if board.dashboard.is_some() {
+        wait(profile.restart_delay);
+    }
+    dashboard::restore();
+    println!("{}", stats.summary());
+    println!("{}", board.capture_stats.summary());
+    println!("\nruntime: {:?}\ngames: {}", start.elapsed(), stats.games);
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,295 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use image::{imageops, RgbaImage};
+use serde::Deserialize;
+
+use crate::{color_to_state, rgb_to_hex, Board, State};
+
+const GROUP_THRESHOLD: f64 = 0.95; // crops this similar are treated as the same glyph when labelling
+const FLAT_DEVIATION: f64 = 2.0; // crops with less deviation than this are a solid colour
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CellReading {
+    Known(State, u8),
+    Start, // safe starting cell (no guess mode)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClassifierKind {
+    Pixel, // one centre pixel per cell, skins with a solid colour per number
+    Template, // compare cell crops against labelled templates
+}
+
+pub enum Classifier {
+    Pixel,
+    Template(TemplateSet),
+}
+
+impl Classifier {
+    pub fn classify(&self, board: &Board, image: &RgbaImage, x: u32, y: u32) -> Result<CellReading, String> {
+        match self {
+            Classifier::Pixel => {
+                let pos = board.get_cell_position_board(x, y).ok_or("cell out of range")?;
+                let pixel = image.get_pixel(pos[0], pos[1]);
+                let color = rgb_to_hex(pixel[0], pixel[1], pixel[2]);
+                if &color == "66DD66" {
+                    return Ok(CellReading::Start);
+                }
+                match color_to_state(&color) {
+                    Some((state, value)) => {Ok(CellReading::Known(state, value))}
+                    None => {Err(format!("unknown color {color}"))}
+                }
+            }
+            Classifier::Template(templates) => {
+                let crop = crop_cell(board, image, x, y).ok_or("cell out of range")?;
+                templates.classify(&crop)
+            }
+        }
+    }
+}
+
+pub fn crop_cell(board: &Board, image: &RgbaImage, x: u32, y: u32) -> Option<RgbaImage> {
+    // cell crop without the outer border, borders are the same for every glyph
+    let pos = board.get_cell_position_board(x, y)?;
+    let margin = board.cell_size / 8;
+    let size = board.cell_size - margin * 2;
+    let left = pos[0] - board.cell_size / 2 + margin;
+    let top = pos[1] - board.cell_size / 2 + margin;
+    if left + size > image.width() || top + size > image.height() {
+        return None;
+    }
+    Some(imageops::crop_imm(image, left, top, size, size).to_image())
+}
+
+/// Mean subtracted copy of a crop's rgb values, used for correlation
+struct Features {
+    values: Vec<f64>,
+    mean: [f64; 3], // average colour, for comparing solid colour crops
+    deviation: f64,
+}
+
+impl Features {
+    fn new(image: &RgbaImage) -> Self {
+        let count = (image.width() * image.height()).max(1) as f64;
+        let mut mean = [0.0; 3];
+        for pixel in image.pixels() {
+            for c in 0..3 {
+                mean[c] += pixel[c] as f64 / count;
+            }
+        }
+        let total_mean = (mean[0] + mean[1] + mean[2]) / 3.0;
+
+        let mut values = Vec::with_capacity(image.len());
+        let mut sum_sq = 0.0;
+        for pixel in image.pixels() {
+            for c in 0..3 {
+                let value = pixel[c] as f64 - total_mean;
+                sum_sq += value * value;
+                values.push(value);
+            }
+        }
+        let deviation = (sum_sq / values.len().max(1) as f64).sqrt();
+        Features {
+            values,
+            mean,
+            deviation,
+        }
+    }
+
+    fn is_flat(&self) -> bool {
+        self.deviation < FLAT_DEVIATION
+    }
+
+    fn correlation(&self, other: &Features) -> f64 {
+        // normalised correlation, 1.0 is identical, solid crops are compared by colour instead
+        if self.is_flat() || other.is_flat() {
+            if self.is_flat() != other.is_flat() {
+                return 0.0;
+            }
+            let distance: f64 = (0..3).map(|c| (self.mean[c] - other.mean[c]).abs()).sum();
+            return (1.0 - distance / 48.0).max(0.0);
+        }
+        let mut dot = 0.0;
+        let mut norm_a = 0.0;
+        let mut norm_b = 0.0;
+        for (a, b) in self.values.iter().zip(&other.values) {
+            dot += a * b;
+            norm_a += a * a;
+            norm_b += b * b;
+        }
+        if norm_a == 0.0 || norm_b == 0.0 {
+            return 0.0;
+        }
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    }
+}
+
+struct Template {
+    reading: CellReading,
+    size: [u32; 2],
+    features: Features,
+}
+
+pub struct TemplateSet {
+    templates: Vec<Template>,
+    threshold: f64, // minimum correlation to accept a match
+}
+
+impl TemplateSet {
+    pub fn load(dir: &Path, threshold: f64) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut templates = Vec::new();
+        if dir.exists() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("png") {
+                    continue;
+                }
+                // files are named <label>-<n>.png
+                let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+                let label = stem.split('-').next().unwrap_or("");
+                let Some(reading) = parse_label(label) else {
+                    continue;
+                };
+                let image = image::open(&path)?.to_rgba8();
+                templates.push(Template {
+                    reading,
+                    size: [image.width(), image.height()],
+                    features: Features::new(&image),
+                });
+            }
+        }
+        Ok(TemplateSet {
+            templates,
+            threshold,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    fn best_match(&self, crop: &RgbaImage) -> Option<(CellReading, f64)> {
+        let mut best: Option<(CellReading, f64)> = None;
+        let features = Features::new(crop);
+        for template in &self.templates {
+            let score = if [crop.width(), crop.height()] == template.size {
+                features.correlation(&template.features)
+            } else {
+                let resized = imageops::resize(crop, template.size[0], template.size[1], imageops::FilterType::Triangle);
+                Features::new(&resized).correlation(&template.features)
+            };
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((template.reading, score));
+            }
+        }
+        best
+    }
+
+    fn classify(&self, crop: &RgbaImage) -> Result<CellReading, String> {
+        match self.best_match(crop) {
+            Some((reading, score)) if score >= self.threshold => {Ok(reading)}
+            Some((reading, score)) => {Err(format!("no template match (best {reading:?} at {score:.2})"))}
+            None => {Err(String::from("no templates loaded"))}
+        }
+    }
+}
+
+fn parse_label(label: &str) -> Option<CellReading> {
+    match label {
+        "closed" => {Some(CellReading::Known(State::Closed, 0))}
+        "flagged" => {Some(CellReading::Known(State::Flagged, 0))}
+        "mine" => {Some(CellReading::Known(State::Mine, 0))}
+        "start" => {Some(CellReading::Start)}
+        _ => {
+            let value = label.parse::<u8>().ok()?;
+            (value <= 8).then_some(CellReading::Known(State::Open, value))
+        }
+    }
+}
+
+fn print_crop(crop: &RgbaImage) {
+    // rough truecolor preview, two pixel rows per character
+    let step = (crop.width() / 16).max(1);
+    let mut y = 0;
+    while y + step < crop.height() {
+        let mut line = String::new();
+        let mut x = 0;
+        while x < crop.width() {
+            let top = crop.get_pixel(x, y);
+            let bottom = crop.get_pixel(x, y + step);
+            // lower half block: foreground is the bottom pixel, background the top one
+            line += &format!(
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▄\x1b[0m",
+                bottom[0], bottom[1], bottom[2], top[0], top[1], top[2],
+            );
+            x += step;
+        }
+        println!("{line}");
+        y += step * 2;
+    }
+}
+
+pub fn label(board: &Board, image: &RgbaImage, dir: &Path, threshold: f64) -> Result<(), Box<dyn std::error::Error>> {
+    // groups identical looking cells and asks for a label for each new group
+    fs::create_dir_all(dir)?;
+    let existing = TemplateSet::load(dir, threshold)?;
+
+    let mut groups: Vec<(RgbaImage, Features, Vec<[u32; 2]>)> = Vec::new();
+    for y in 1..board.grid_size[1] + 1 {
+        for x in 1..board.grid_size[0] + 1 {
+            let Some(crop) = crop_cell(board, image, x, y) else {
+                continue;
+            };
+            let features = Features::new(&crop);
+            match groups.iter_mut().find(|(_, group, _)| group.correlation(&features) >= GROUP_THRESHOLD) {
+                Some((_, _, cells)) => {cells.push([x, y])}
+                None => {groups.push((crop, features, vec![[x, y]]))}
+            }
+        }
+    }
+
+    let mut saved = 0;
+    for (crop, _, cells) in &groups {
+        if let Some((reading, score)) = existing.best_match(crop)
+            && score >= threshold {
+            println!("{} cells already match {reading:?} ({score:.2}), skipping", cells.len());
+            continue;
+        }
+
+        println!();
+        print_crop(crop);
+        println!("{} cells, e.g. {:?}", cells.len(), &cells[..cells.len().min(5)]);
+        let label = loop {
+            print!("label (closed, flagged, mine, start, 0-8, or empty to skip): ");
+            io::stdout().flush()?;
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+            let input = input.trim().to_lowercase();
+            if input.is_empty() || parse_label(&input).is_some() {
+                break input;
+            }
+            println!("unknown label '{input}'");
+        };
+        if label.is_empty() {
+            continue;
+        }
+
+        // several templates per label are fine, e.g. anti-aliased variants
+        let mut n = 1;
+        let mut path = PathBuf::from(dir).join(format!("{label}-{n}.png"));
+        while path.exists() {
+            n += 1;
+            path = PathBuf::from(dir).join(format!("{label}-{n}.png"));
+        }
+        crop.save(&path)?;
+        saved += 1;
+        println!("saved {}", path.display());
+    }
+    println!("\nsaved {saved} new templates to {}", dir.display());
+    Ok(())
+}
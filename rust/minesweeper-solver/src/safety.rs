@@ -0,0 +1,115 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicI64, AtomicU32, Ordering},
+    Mutex,
+};
+
+// set from the global key listener, the solver runs on the main thread
+pub static PAUSED: AtomicBool = AtomicBool::new(false);
+pub static STEP: AtomicBool = AtomicBool::new(false);
+// a single step was let through, it runs on until the next decision
+static STEPPING: AtomicBool = AtomicBool::new(false);
+static PAUSE_REASON: Mutex<String> = Mutex::new(String::new());
+
+// where the solver last put the mouse, i64::MIN when it hasn't moved it yet
+static EXPECTED_X: AtomicI64 = AtomicI64::new(i64::MIN);
+static EXPECTED_Y: AtomicI64 = AtomicI64::new(i64::MIN);
+
+// primary monitor bounds, for the corner fail-safe
+static SCREEN_X: AtomicI64 = AtomicI64::new(0);
+static SCREEN_Y: AtomicI64 = AtomicI64::new(0);
+static SCREEN_W: AtomicI64 = AtomicI64::new(0);
+static SCREEN_H: AtomicI64 = AtomicI64::new(0);
+static FAILSAFE_MARGIN: AtomicU32 = AtomicU32::new(0);
+
+pub fn toggle_pause() {
+    STEPPING.store(false, Ordering::Relaxed);
+    if PAUSED.fetch_xor(true, Ordering::Relaxed) {
+        // was paused, resuming
+        PAUSE_REASON.lock().unwrap().clear();
+    } else {
+        *PAUSE_REASON.lock().unwrap() = String::from("paused by user");
+    }
+}
+
+pub fn pause(reason: &str) {
+    STEPPING.store(false, Ordering::Relaxed);
+    *PAUSE_REASON.lock().unwrap() = reason.to_string();
+    PAUSED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+pub fn pause_reason() -> String {
+    PAUSE_REASON.lock().unwrap().clone()
+}
+
+pub fn step() {
+    STEP.store(true, Ordering::Relaxed);
+}
+
+pub fn is_held() -> bool {
+    // paused, and not in the middle of a single step
+    is_paused() && !STEPPING.load(Ordering::Relaxed)
+}
+
+pub fn start_step() {
+    STEPPING.store(true, Ordering::Relaxed);
+}
+
+pub fn end_step() {
+    // reached the next decision, a step pressed while running doesn't carry over
+    STEPPING.store(false, Ordering::Relaxed);
+    if !is_paused() {
+        STEP.store(false, Ordering::Relaxed);
+    }
+}
+
+pub fn set_screen(x: i32, y: i32, width: u32, height: u32, margin: u32) {
+    SCREEN_X.store(x as i64, Ordering::Relaxed);
+    SCREEN_Y.store(y as i64, Ordering::Relaxed);
+    SCREEN_W.store(width as i64, Ordering::Relaxed);
+    SCREEN_H.store(height as i64, Ordering::Relaxed);
+    FAILSAFE_MARGIN.store(margin, Ordering::Relaxed);
+}
+
+pub fn in_corner(x: f64, y: f64) -> bool {
+    // moving the mouse into any corner of the primary screen stops the solver
+    let margin = FAILSAFE_MARGIN.load(Ordering::Relaxed) as f64;
+    let width = SCREEN_W.load(Ordering::Relaxed) as f64;
+    if margin == 0.0 || width == 0.0 {
+        return false;
+    }
+    let left = SCREEN_X.load(Ordering::Relaxed) as f64;
+    let top = SCREEN_Y.load(Ordering::Relaxed) as f64;
+    let right = left + width - 1.0;
+    let bottom = top + SCREEN_H.load(Ordering::Relaxed) as f64 - 1.0;
+
+    let near_x = x <= left + margin || x >= right - margin;
+    let near_y = y <= top + margin || y >= bottom - margin;
+    near_x && near_y
+}
+
+pub fn set_expected(x: u32, y: u32) {
+    EXPECTED_X.store(x as i64, Ordering::Relaxed);
+    EXPECTED_Y.store(y as i64, Ordering::Relaxed);
+}
+
+pub fn clear_expected() {
+    // after a pause the user has had the mouse, don't compare against the old spot
+    EXPECTED_X.store(i64::MIN, Ordering::Relaxed);
+    EXPECTED_Y.store(i64::MIN, Ordering::Relaxed);
+}
+
+pub fn user_moved(x: i32, y: i32, tolerance: u32) -> bool {
+    // the mouse isn't where the solver left it, so someone else moved it
+    let expected_x = EXPECTED_X.load(Ordering::Relaxed);
+    let expected_y = EXPECTED_Y.load(Ordering::Relaxed);
+    if tolerance == 0 || expected_x == i64::MIN {
+        return false;
+    }
+    let dx = (x as i64 - expected_x).abs();
+    let dy = (y as i64 - expected_y).abs();
+    dx > tolerance as i64 || dy > tolerance as i64
+}
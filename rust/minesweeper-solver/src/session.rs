@@ -0,0 +1,187 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameOutcome {
+    Won,
+    Lost,
+    StepLimit, // gave up after hitting the step limit
+}
+
+impl GameOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            GameOutcome::Won => {"won"}
+            GameOutcome::Lost => {"lost"}
+            GameOutcome::StepLimit => {"step_limit"}
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GameResult {
+    pub game: u32, // game number in this session (starting at 1)
+    pub outcome: GameOutcome,
+    pub time: f64, // seconds
+    pub steps: u32,
+    pub guesses: u32, // random clicks made without a logical reason
+}
+
+#[derive(Default)]
+pub struct SessionStats {
+    pub games: u32,
+    pub won: u32,
+    pub lost: u32,
+    pub step_limit: u32,
+    pub total_time: f64,
+    pub total_steps: u32,
+    pub total_guesses: u32,
+    pub best_time: Option<f64>, // fastest win
+}
+
+impl SessionStats {
+    pub fn add(&mut self, result: &GameResult) {
+        self.games += 1;
+        self.total_time += result.time;
+        self.total_steps += result.steps;
+        self.total_guesses += result.guesses;
+        match result.outcome {
+            GameOutcome::Won => {
+                self.won += 1;
+                if self.best_time.is_none_or(|best| result.time < best) {
+                    self.best_time = Some(result.time);
+                }
+            }
+            GameOutcome::Lost => {self.lost += 1}
+            GameOutcome::StepLimit => {self.step_limit += 1}
+        }
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        self.won as f64 / self.games as f64 * 100.0
+    }
+
+    pub fn summary(&self) -> String {
+        let games = self.games.max(1) as f64;
+        let best = self.best_time.map_or(String::from("--"), |time| format!("{time:.1}s"));
+        format!(
+            "won {}/{} ({:.1}%) | lost {} | gave up {} | avg {:.1}s, {:.0} steps, {:.1} guesses | best {best}",
+            self.won,
+            self.games,
+            self.win_rate(),
+            self.lost,
+            self.step_limit,
+            self.total_time / games,
+            self.total_steps as f64 / games,
+            self.total_guesses as f64 / games,
+        )
+    }
+}
+
+pub fn result_line(result: &GameResult, games: u32) -> String {
+    format!(
+        "game {}/{games}: {} in {:.1}s, {} steps, {} guesses",
+        result.game,
+        result.outcome.as_str(),
+        result.time,
+        result.steps,
+        result.guesses,
+    )
+}
+
+enum LogFormat {
+    Csv,
+    Json,
+}
+
+const CSV_HEADER: &str = "session,finished,game,outcome,time,steps,guesses";
+
+/// A result as it's logged, with the session it's from
+#[derive(Serialize)]
+struct LoggedResult<'a> {
+    session: u64,
+    finished: u64,
+    #[serde(flatten)]
+    result: &'a GameResult,
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+/// Writes per-game results to disk as each game finishes, so quitting mid session keeps them
+/// Earlier sessions are kept, each row has its session's start time to tell runs apart
+pub struct ResultLog {
+    path: PathBuf,
+    format: LogFormat,
+    session: u64, // when the session started, in unix seconds
+}
+
+impl ResultLog {
+    pub fn new(path: &Path) -> Self {
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => {LogFormat::Json}
+            _ => {LogFormat::Csv}
+        };
+        ResultLog {
+            path: path.to_path_buf(),
+            format,
+            session: unix_time(),
+        }
+    }
+
+    pub fn write(&mut self, result: &GameResult) -> Result<(), Box<dyn std::error::Error>> {
+        let entry = LoggedResult {
+            session: self.session,
+            finished: unix_time(),
+            result,
+        };
+        match self.format {
+            LogFormat::Csv => {
+                // append, so results from older sessions are kept
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?;
+                if file.metadata()?.len() == 0 {
+                    writeln!(file, "{CSV_HEADER}")?;
+                }
+                writeln!(
+                    file,
+                    "{},{},{},{},{:.3},{},{}",
+                    entry.session,
+                    entry.finished,
+                    result.game,
+                    result.outcome.as_str(),
+                    result.time,
+                    result.steps,
+                    result.guesses,
+                )?;
+            }
+            LogFormat::Json => {
+                // read back and rewritten as a whole array
+                let mut entries: Vec<serde_json::Value> = match fs::read_to_string(&self.path) {
+                    Ok(text) if !text.trim().is_empty() => {
+                        // don't overwrite a log that can't be read
+                        serde_json::from_str(&text)?
+                    }
+                    _ => {Vec::new()}
+                };
+                entries.push(serde_json::to_value(&entry)?);
+                let file = File::create(&self.path)?;
+                serde_json::to_writer_pretty(file, &entries)?;
+            }
+        }
+        Ok(())
+    }
+}
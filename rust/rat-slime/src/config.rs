@@ -0,0 +1,532 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+};
+use serde::Deserialize;
+use crate::data_dir;
+
+/// Where keys are looked up, each pane that takes keys has its own bindings
+#[derive(Copy, Clone, PartialEq)]
+pub enum Context {
+    Player,
+    FilePicker,
+    Effects,
+    Library,
+    Stats,
+    Queue,
+}
+
+impl Context {
+    const ALL: [Context; 6] = [
+        Context::Player,
+        Context::FilePicker,
+        Context::Effects,
+        Context::Library,
+        Context::Stats,
+        Context::Queue,
+    ];
+
+    /// Table name in the config file
+    fn name(&self) -> &'static str {
+        match self {
+            Context::Player => "player",
+            Context::FilePicker => "file_picker",
+            Context::Effects => "effects",
+            Context::Library => "library",
+            Context::Stats => "stats",
+            Context::Queue => "queue",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Context::Player => "Player",
+            Context::FilePicker => "File Picker",
+            Context::Effects => "Effects",
+            Context::Library => "Library",
+            Context::Stats => "Stats",
+            Context::Queue => "Queue",
+        }
+    }
+
+    /// Actions of the context, with their name in the config file and default keys
+    fn actions(&self) -> &'static [(Action, &'static str, &'static [&'static str])] {
+        match self {
+            Context::Player => &[
+                (Action::Quit, "quit", &["q", "backspace", "esc"]),
+                (Action::Help, "help", &["?"]),
+                (Action::FilePicker, "file_picker", &["tab"]),
+                (Action::Library, "library", &["/"]),
+                (Action::Effects, "effects", &["e"]),
+                (Action::Lyrics, "lyrics", &["y"]),
+                (Action::Stats, "stats", &["i"]),
+                (Action::Queue, "queue", &["u"]),
+                (Action::TogglePlaying, "toggle_playing", &["space"]),
+                (Action::Next, "next", &["enter"]),
+                (Action::Previous, "previous", &["b"]),
+                (Action::VolumeUp, "volume_up", &["up"]),
+                (Action::VolumeDown, "volume_down", &["down"]),
+                (Action::SpeedDown, "speed_down", &["o"]),
+                (Action::SpeedUp, "speed_up", &["p"]),
+                (Action::KeepPitch, "keep_pitch", &["t"]),
+                (Action::PitchDown, "pitch_down", &[","]),
+                (Action::PitchUp, "pitch_up", &["."]),
+                (Action::SeekBackward, "seek_backward", &["left"]),
+                (Action::SeekForward, "seek_forward", &["right"]),
+                (Action::SeekStepDown, "seek_step_down", &["["]),
+                (Action::SeekStepUp, "seek_step_up", &["]"]),
+                (Action::Jump(0), "jump_0", &["0"]),
+                (Action::Jump(1), "jump_10", &["1"]),
+                (Action::Jump(2), "jump_20", &["2"]),
+                (Action::Jump(3), "jump_30", &["3"]),
+                (Action::Jump(4), "jump_40", &["4"]),
+                (Action::Jump(5), "jump_50", &["5"]),
+                (Action::Jump(6), "jump_60", &["6"]),
+                (Action::Jump(7), "jump_70", &["7"]),
+                (Action::Jump(8), "jump_80", &["8"]),
+                (Action::Jump(9), "jump_90", &["9"]),
+                (Action::Shuffle, "shuffle", &["s"]),
+                (Action::SmartShuffle, "smart_shuffle", &["S"]),
+                (Action::Loop, "loop", &["l"]),
+                (Action::Crossfade, "crossfade", &["x"]),
+                (Action::GainMode, "gain_mode", &["g"]),
+                (Action::SavePlaylist, "save_playlist", &["w"]),
+            ],
+            Context::FilePicker => &[
+                (Action::Back, "back", &["tab", "esc"]),
+                (Action::Up, "up", &["up", "k"]),
+                (Action::Down, "down", &["down", "j"]),
+                (Action::Open, "open", &["enter", "right"]),
+                (Action::Leave, "leave", &["backspace", "left"]),
+                (Action::Replace, "replace", &["r"]),
+                (Action::Add, "add", &["a"]),
+                (Action::Playlists, "playlists", &["p"]),
+            ],
+            Context::Effects => &[
+                (Action::Back, "back", &["tab", "esc", "e"]),
+                (Action::Up, "up", &["up", "k"]),
+                (Action::Down, "down", &["down", "j"]),
+                (Action::Decrease, "decrease", &["left", "h"]),
+                (Action::Increase, "increase", &["right", "l"]),
+                (Action::Reset, "reset", &["0", "delete"]),
+            ],
+            Context::Library => &[
+                (Action::Back, "back", &["esc"]),
+                (Action::NextView, "next_view", &["tab"]),
+                (Action::PreviousView, "previous_view", &["backtab"]),
+                (Action::Up, "up", &["up"]),
+                (Action::Down, "down", &["down"]),
+                (Action::Open, "open", &["enter"]),
+                (Action::AddAll, "add_all", &["ctrl+a"]),
+            ],
+            Context::Stats => &[
+                (Action::Back, "back", &["esc", "i"]),
+                (Action::NextView, "next_view", &["tab"]),
+                (Action::PreviousView, "previous_view", &["backtab"]),
+                (Action::PreviousPeriod, "previous_period", &["left", "h"]),
+                (Action::NextPeriod, "next_period", &["right", "l"]),
+                (Action::Up, "up", &["up", "k"]),
+                (Action::Down, "down", &["down", "j"]),
+            ],
+            Context::Queue => &[
+                (Action::Back, "back", &["esc", "tab", "u"]),
+                (Action::Up, "up", &["up", "k"]),
+                (Action::Down, "down", &["down", "j"]),
+                (Action::MoveUp, "move_up", &["K", "shift+up"]),
+                (Action::MoveDown, "move_down", &["J", "shift+down"]),
+                (Action::Open, "jump", &["enter"]),
+                (Action::PlayNext, "play_next", &["n"]),
+                (Action::Remove, "remove", &["d", "delete"]),
+                (Action::Clear, "clear", &["c"]),
+                (Action::SavePlaylist, "save_playlist", &["w"]),
+            ],
+        }
+    }
+}
+
+/// Something a key can be bound to
+#[derive(Copy, Clone, PartialEq)]
+pub enum Action {
+    Quit,
+    Help,
+    FilePicker,
+    Library,
+    Effects,
+    Lyrics,
+    Stats,
+    Queue,
+    TogglePlaying,
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+    SpeedDown,
+    SpeedUp,
+    KeepPitch,
+    PitchDown,
+    PitchUp,
+    SeekBackward,
+    SeekForward,
+    SeekStepDown,
+    SeekStepUp,
+    /// Jump to a tenth of the song
+    Jump(u8),
+    Shuffle,
+    SmartShuffle,
+    Loop,
+    Crossfade,
+    GainMode,
+    SavePlaylist,
+    Back,
+    Up,
+    Down,
+    Open,
+    Leave,
+    Replace,
+    Add,
+    Playlists,
+    Decrease,
+    Increase,
+    Reset,
+    NextView,
+    PreviousView,
+    AddAll,
+    PreviousPeriod,
+    NextPeriod,
+    MoveUp,
+    MoveDown,
+    PlayNext,
+    Remove,
+    Clear,
+}
+
+/// A key with the ctrl and alt modifiers it needs, shift is part of the character
+#[derive(Copy, Clone, PartialEq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Parses keys like "q", "space", "ctrl+a", "shift+tab" or "f5"
+    fn parse(text: &str) -> Option<Key> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text.trim();
+        // "+" on its own, or at the end of "ctrl++", is the key itself
+        while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers |= KeyModifiers::CONTROL,
+                "alt" => modifiers |= KeyModifiers::ALT,
+                "shift" => modifiers |= KeyModifiers::SHIFT,
+                _ => return None,
+            }
+            rest = key;
+        }
+
+        let code = match rest.to_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            name => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::Char(c.to_ascii_uppercase()),
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => KeyCode::F(name.strip_prefix('f')?.parse().ok().filter(|n| (1..=24).contains(n))?),
+                }
+            }
+        };
+        Some(Key::new(code, modifiers))
+    }
+
+    /// Shift only counts for keys that don't have a shifted character of their own
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT),
+            _ => modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT),
+        };
+        Key { code, modifiers }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Key {
+        Key::new(event.code, event.modifiers)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            _ => write!(f, "?"),
+        }
+    }
+}
+
+struct Binding {
+    context: Context,
+    action: Action,
+    name: &'static str,
+    keys: Vec<Key>,
+}
+
+/// Keys bound to every action, the defaults with whatever the config file changes
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Keymap {
+    /// Action bound to a key in a context
+    pub fn action(&self, context: Context, event: KeyEvent) -> Option<Action> {
+        let key = Key::from(event);
+        self.bindings
+            .iter()
+            .find(|binding| binding.context == context && binding.keys.contains(&key))
+            .map(|binding| binding.action)
+    }
+
+    /// First key bound to an action, for the hints in pane titles
+    pub fn key(&self, context: Context, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|binding| binding.context == context && binding.action == action)
+            .and_then(|binding| binding.keys.first())
+            .map_or("-".to_string(), Key::to_string)
+    }
+
+    /// Hint line like " ⏎ open | esc back ", with the keys currently bound
+    pub fn hints(&self, context: Context, hints: &[(Action, &str)]) -> String {
+        let hints: Vec<String> = hints
+            .iter()
+            .map(|(action, hint)| format!("{} {hint}", self.key(context, *action)))
+            .collect();
+        format!(" {} ", hints.join(" | "))
+    }
+
+    /// Every binding, grouped by context, for the help overlay
+    pub fn help(&self, scroll: usize, height: usize) -> Text<'static> {
+        let palette = palette();
+        let mut lines = Vec::new();
+        for context in Context::ALL {
+            if !lines.is_empty() {
+                lines.push(Line::from(""));
+            }
+            lines.push(Line::from(Span::styled(
+                format!(" {}", context.title()),
+                Style::default().fg(palette.accent).add_modifier(Modifier::BOLD),
+            )));
+            for binding in self.bindings.iter().filter(|binding| binding.context == context) {
+                let keys: Vec<String> = binding.keys.iter().map(Key::to_string).collect();
+                let keys = if keys.is_empty() { "unbound".to_string() } else { keys.join(", ") };
+                lines.push(Line::from(vec![
+                    Span::styled(format!("   {:<16}", binding.name.replace('_', " ")), Style::default().fg(palette.text)),
+                    Span::styled(keys, Style::default().fg(palette.value)),
+                ]));
+            }
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!(" typing in the library search goes to the query, config: {}", config_path().display()),
+            Style::default().fg(palette.dim),
+        )));
+
+        let scroll = scroll.min(lines.len().saturating_sub(height));
+        Text::from(lines.into_iter().skip(scroll).take(height).collect::<Vec<_>>())
+    }
+}
+
+/// Named colours the panes are drawn with
+pub struct Palette {
+    /// Labels and plain text
+    pub text: Color,
+    /// Values next to labels
+    pub value: Color,
+    /// Selection markers, focused borders, artists and messages
+    pub highlight: Color,
+    /// Headings like the queue's Loop: and Next:
+    pub accent: Color,
+    /// Song titles and folders
+    pub title: Color,
+    /// The face, the progress bar, playlists and sliders
+    pub special: Color,
+    /// Things in the background, and the selected row
+    pub dim: Color,
+    pub error: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            text: Color::White,
+            value: Color::Yellow,
+            highlight: Color::LightYellow,
+            accent: Color::LightRed,
+            title: Color::LightCyan,
+            special: Color::LightMagenta,
+            dim: Color::DarkGray,
+            error: Color::Red,
+        }
+    }
+}
+
+impl Palette {
+    fn set(&mut self, name: &str, color: Color) -> bool {
+        let slot = match name {
+            "text" => &mut self.text,
+            "value" => &mut self.value,
+            "highlight" => &mut self.highlight,
+            "accent" => &mut self.accent,
+            "title" => &mut self.title,
+            "special" => &mut self.special,
+            "dim" => &mut self.dim,
+            "error" => &mut self.error,
+            _ => return false,
+        };
+        *slot = color;
+        true
+    }
+}
+
+static PALETTE: OnceLock<Palette> = OnceLock::new();
+
+/// Colours from the config file, the defaults until it's been loaded
+pub fn palette() -> &'static Palette {
+    PALETTE.get_or_init(Palette::default)
+}
+
+/// Contents of config.toml, every action and colour can be left out
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    /// Keys by context and then action, like [keys.player] volume_up = ["k", "up"]
+    #[serde(default)]
+    keys: HashMap<String, HashMap<String, Keys>>,
+    /// Colour names or #rrggbb, like [colors] value = "#ffaa00"
+    #[serde(default)]
+    colors: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn config_path() -> PathBuf {
+    data_dir().join("config.toml")
+}
+
+/// Loads config.toml, sets the palette and returns the keymap
+///
+/// Mistakes in the file are skipped and described in the returned messages, so one typo doesn't
+/// take every other setting with it.
+pub fn load() -> (Keymap, Vec<String>) {
+    let mut problems = Vec::new();
+    let raw = match fs::read_to_string(config_path()) {
+        Ok(text) => toml::from_str::<RawConfig>(&text).unwrap_or_else(|error| {
+            problems.push(format!("config.toml: {}", error.message()));
+            RawConfig::default()
+        }),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => RawConfig::default(),
+        Err(error) => {
+            problems.push(format!("config.toml: {error}"));
+            RawConfig::default()
+        }
+    };
+
+    let mut bindings = Vec::new();
+    for context in Context::ALL {
+        let mut overrides = raw.keys.get(context.name()).cloned().unwrap_or_default();
+        for &(action, name, defaults) in context.actions() {
+            let keys = match overrides.remove(name) {
+                Some(Keys::One(key)) => vec![key],
+                Some(Keys::Many(keys)) => keys,
+                None => defaults.iter().map(|key| key.to_string()).collect(),
+            };
+            let keys = keys
+                .iter()
+                .filter_map(|text| {
+                    let key = Key::parse(text);
+                    if key.is_none() {
+                        problems.push(format!("config.toml: unknown key \"{text}\" for {}.{name}", context.name()));
+                    }
+                    key
+                })
+                .collect();
+            bindings.push(Binding { context, action, name, keys });
+        }
+        for name in overrides.keys() {
+            problems.push(format!("config.toml: unknown action {}.{name}", context.name()));
+        }
+    }
+    for name in raw.keys.keys().filter(|name| Context::ALL.iter().all(|context| context.name() != *name)) {
+        problems.push(format!("config.toml: unknown key table {name}"));
+    }
+
+    let mut palette = Palette::default();
+    for (name, value) in &raw.colors {
+        match value.parse::<Color>() {
+            Ok(color) => {
+                if !palette.set(name, color) {
+                    problems.push(format!("config.toml: unknown colour {name}"));
+                }
+            }
+            Err(_) => {
+                problems.push(format!("config.toml: can't read colour \"{value}\" for {name}"));
+            }
+        }
+    }
+    // only ever set once, before anything is drawn
+    let _ = PALETTE.set(palette);
+
+    (Keymap { bindings }, problems)
+}
@@ -0,0 +1,201 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use color_eyre::eyre::eyre;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A position or volume, either set outright or moved by an amount
+#[derive(Clone, Copy)]
+pub enum Amount {
+    To(f64),
+    By(f64),
+}
+
+impl Amount {
+    fn parse(text: &str) -> Option<Amount> {
+        let text = text.trim();
+        let value = text.parse::<f64>().ok().filter(|value| value.is_finite())?;
+        if text.starts_with(['+', '-']) {
+            Some(Amount::By(value))
+        } else {
+            Some(Amount::To(value))
+        }
+    }
+
+    pub fn apply(&self, current: f64) -> f64 {
+        match self {
+            Amount::To(value) => *value,
+            Amount::By(value) => current + value,
+        }
+    }
+}
+
+pub enum Command {
+    Play,
+    Pause,
+    Toggle,
+    Next,
+    Previous,
+    /// Seconds into the song
+    Seek(Amount),
+    /// Percent, 0 to 100
+    Volume(Amount),
+    /// A song, folder or playlist to add to the end of the queue
+    Enqueue(PathBuf),
+    NowPlaying,
+}
+
+/// JSON form of a command, like {"command": "seek", "value": "+10"}
+#[derive(Deserialize)]
+struct JsonCommand {
+    command: String,
+    #[serde(default)]
+    value: Value,
+}
+
+impl Command {
+    /// Parses a command line, "seek +10", or the same as a JSON object
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let (name, argument) = if line.starts_with('{') {
+            let json: JsonCommand = serde_json::from_str(line).map_err(|error| error.to_string())?;
+            let argument = match json.value {
+                Value::Null => String::new(),
+                Value::String(text) => text,
+                value => value.to_string(),
+            };
+            (json.command, argument)
+        } else {
+            let (name, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            (name.to_string(), argument.trim().to_string())
+        };
+
+        let amount = || Amount::parse(&argument).ok_or(format!("{name} needs a number, like 30, +10 or -10"));
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "play" => Ok(Command::Play),
+            "pause" => Ok(Command::Pause),
+            "toggle" | "play-pause" => Ok(Command::Toggle),
+            "next" | "skip" => Ok(Command::Next),
+            "previous" | "prev" => Ok(Command::Previous),
+            "seek" => Ok(Command::Seek(amount()?)),
+            "volume" => Ok(Command::Volume(amount()?)),
+            "enqueue" | "add" => {
+                if argument.is_empty() {
+                    Err("enqueue needs a path".to_string())
+                } else {
+                    Ok(Command::Enqueue(PathBuf::from(argument)))
+                }
+            }
+            "now-playing" | "status" => Ok(Command::NowPlaying),
+            "" => Err("empty command".to_string()),
+            other => Err(format!("unknown command: {other}")),
+        }
+    }
+}
+
+/// A command from a client, and where to send the JSON reply to it
+pub struct Request {
+    pub command: Result<Command, String>,
+    pub reply: Sender<String>,
+}
+
+/// Where the running player listens for commands
+pub fn socket_path() -> PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+    let user = std::env::var("USER").unwrap_or_default();
+    dir.join(format!("rat-slime-{user}.sock"))
+}
+
+/// Listens on the control socket, commands are handed to the main loop through `requests`
+pub struct Server {
+    pub requests: Receiver<Request>,
+    path: PathBuf,
+}
+
+impl Server {
+    /// Starts listening, fails if another player already has the socket
+    pub fn start() -> io::Result<Server> {
+        let path = socket_path();
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(io::Error::new(io::ErrorKind::AddrInUse, "another rat-slime is listening"));
+            }
+            // left behind by a player that didn't exit cleanly
+            fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || serve(stream, sender));
+            }
+        });
+        Ok(Server { requests, path })
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Answers a client's commands one line at a time, until it hangs up or the player exits
+fn serve(stream: UnixStream, sender: Sender<Request>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (reply, replies) = mpsc::channel();
+        let request = Request { command: Command::parse(&line), reply };
+        if sender.send(request).is_err() {
+            return;
+        }
+        let Ok(reply) = replies.recv() else {
+            return;
+        };
+        if writeln!(writer, "{reply}").is_err() {
+            return;
+        }
+    }
+}
+
+/// `rat-slime ctl <command> [value]`, sends a command to the running player and prints the reply
+pub fn client(args: &[String]) -> color_eyre::Result<()> {
+    let Some(name) = args.first() else {
+        return Err(eyre!(
+            "usage: rat-slime ctl <play|pause|toggle|next|previous|seek <s>|volume <%>|enqueue <path>|now-playing>"
+        ));
+    };
+    let mut argument = args[1..].join(" ");
+    // the player has its own working directory
+    if matches!(name.as_str(), "enqueue" | "add") && !argument.is_empty() {
+        let path = fs::canonicalize(&argument).map_err(|error| eyre!("{argument}: {error}"))?;
+        argument = path.to_string_lossy().to_string();
+    }
+
+    let path = socket_path();
+    let stream = UnixStream::connect(&path).map_err(|error| eyre!("no player at {}: {error}", path.display()))?;
+    writeln!(&stream, "{name} {argument}")?;
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    println!("{}", reply.trim_end());
+
+    // the reply already says what went wrong, just fail for scripts checking the exit code
+    let ok = serde_json::from_str::<Value>(&reply).ok().and_then(|reply| reply["ok"].as_bool());
+    if ok != Some(true) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
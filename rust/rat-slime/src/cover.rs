@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbImage};
+use lofty::file::TaggedFileExt;
+use lofty::picture::PictureType;
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span, Text},
+};
+use crate::config::palette;
+
+/// Pictures looked for next to the track when it has none embedded
+const COVER_FILES: [&str; 4] = ["cover", "folder", "front", "album"];
+const COVER_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+/// Largest side kept after loading, panes are never anywhere near this big
+const MAX_SIZE: u32 = 256;
+
+/// Album cover of the current song, drawn with half blocks in truecolor
+pub struct Cover {
+    /// Track the picture was loaded for
+    song: Option<PathBuf>,
+    image: Option<DynamicImage>,
+    /// Size in cells the picture was last rendered at
+    size: (usize, usize),
+    rendered: Text<'static>,
+}
+
+impl Cover {
+    pub fn new() -> Self {
+        Self {
+            song: None,
+            image: None,
+            size: (0, 0),
+            rendered: Text::default(),
+        }
+    }
+
+    /// Loads the picture when the song changes and re-renders it when the pane size does
+    pub fn update(&mut self, song: Option<&Path>, width: usize, height: usize) {
+        if self.song.as_deref() != song {
+            self.song = song.map(Path::to_path_buf);
+            self.image = song.and_then(load);
+            self.size = (0, 0);
+        }
+        if self.size != (width, height) {
+            self.size = (width, height);
+            self.rendered = match &self.image {
+                Some(image) => render(image, width, height),
+                None => placeholder(width, height),
+            };
+        }
+    }
+
+    pub fn content(&self) -> Text<'static> {
+        self.rendered.clone()
+    }
+}
+
+/// Embedded front cover, otherwise any embedded picture, otherwise a cover file in the track's folder
+fn load(path: &Path) -> Option<DynamicImage> {
+    let image = embedded(path)
+        .or_else(|| embedded_id3(path))
+        .and_then(|data| image::load_from_memory(&data).ok())
+        .or_else(|| folder_cover(path))?;
+    // covers are often 1000px+, scaling them down once keeps every resize after that cheap
+    Some(image.resize(MAX_SIZE, MAX_SIZE, FilterType::Triangle))
+}
+
+fn embedded(path: &Path) -> Option<Vec<u8>> {
+    let tagged_file = lofty::read_from_path(path).ok()?;
+    let pictures: Vec<_> = tagged_file.tags().iter().flat_map(|tag| tag.pictures()).collect();
+    let picture = pictures
+        .iter()
+        .find(|picture| picture.pic_type() == PictureType::CoverFront)
+        .or(pictures.first())?;
+    Some(picture.data().to_vec())
+}
+
+/// id3 tags lofty couldn't make sense of, e.g. on files with a broken mpeg stream
+fn embedded_id3(path: &Path) -> Option<Vec<u8>> {
+    let tag = id3::Tag::read_from_path(path).ok()?;
+    let picture = tag
+        .pictures()
+        .find(|picture| picture.picture_type == id3::frame::PictureType::CoverFront)
+        .or(tag.pictures().next())?;
+    Some(picture.data.clone())
+}
+
+fn folder_cover(path: &Path) -> Option<DynamicImage> {
+    let dir = path.parent()?;
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let file = entry.path();
+        let stem = file.file_stem().and_then(|s| s.to_str()).map(str::to_lowercase);
+        let extension = file.extension().and_then(|s| s.to_str()).map(str::to_lowercase);
+        let (Some(stem), Some(extension)) = (stem, extension) else {
+            continue;
+        };
+        if COVER_FILES.contains(&stem.as_str()) && COVER_EXTENSIONS.contains(&extension.as_str()) {
+            if let Ok(image) = image::open(&file) {
+                return Some(image);
+            }
+        }
+    }
+    None
+}
+
+/// Scales the picture to fit the cells, keeping its aspect ratio, and centers it
+fn render(image: &DynamicImage, width: usize, height: usize) -> Text<'static> {
+    if width == 0 || height == 0 {
+        return Text::default();
+    }
+    // a cell is about twice as tall as it is wide, so with two pixels per cell the pixels come out square
+    let image: RgbImage = image.resize(width as u32, height as u32 * 2, FilterType::Triangle).to_rgb8();
+    let (image_width, image_height) = (image.width() as usize, image.height() as usize);
+    let rows = image_height.div_ceil(2);
+    let pad_top = (height - rows.min(height)) / 2;
+    let pad_left = " ".repeat((width - image_width.min(width)) / 2);
+
+    let mut lines = vec![Line::from(""); pad_top];
+    for row in 0..rows {
+        let mut spans = vec![Span::raw(pad_left.clone())];
+        for x in 0..image_width {
+            let [r, g, b] = image.get_pixel(x as u32, row as u32 * 2).0;
+            let mut style = Style::default().fg(Color::Rgb(r, g, b));
+            // the last row of an odd height picture only has a top half
+            if row * 2 + 1 < image_height {
+                let [r, g, b] = image.get_pixel(x as u32, row as u32 * 2 + 1).0;
+                style = style.bg(Color::Rgb(r, g, b));
+            }
+            spans.push(Span::styled("▀", style));
+        }
+        lines.push(Line::from(spans));
+    }
+    Text::from(lines)
+}
+
+fn placeholder(width: usize, height: usize) -> Text<'static> {
+    let mut lines = vec![Line::from(""); height.saturating_sub(1) / 2];
+    let text = "no cover";
+    lines.push(Line::from(Span::styled(
+        format!("{:pad$}{text}", "", pad = width.saturating_sub(text.len()) / 2),
+        Style::default().fg(palette().dim),
+    )));
+    Text::from(lines)
+}
@@ -0,0 +1,463 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+};
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+use serde::{Deserialize, Serialize};
+use crate::config::palette;
+use crate::data_dir;
+
+/// Center frequencies of the EQ bands, an octave apart
+pub const BAND_FREQUENCIES: [f32; 10] = [31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+/// Q of an octave wide band
+const BAND_Q: f32 = 1.41;
+const MAX_GAIN: f32 = 12.0;
+const BASS_FREQUENCY: f32 = 100.0;
+const MAX_WIDTH: f32 = 2.0;
+/// Peak level the limiter keeps under, -1 dBFS
+const LIMITER_THRESHOLD: f32 = 0.89;
+/// Seconds the limiter takes to let go after a peak
+const LIMITER_RELEASE: f32 = 0.1;
+/// Frames between checks for changed settings, short enough for the sliders to feel live
+const CHECK_FRAMES: usize = 256;
+
+pub const PRESETS: [(&str, [f32; 10]); 10] = [
+    ("Flat", [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+    ("Bass", [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+    ("Treble", [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 4.0, 5.0, 6.0]),
+    ("Vocal", [-2.0, -2.0, -1.0, 1.0, 3.0, 4.0, 3.0, 1.0, 0.0, -1.0]),
+    ("Rock", [4.0, 3.0, 2.0, 0.0, -1.0, -1.0, 0.0, 2.0, 3.0, 4.0]),
+    ("Pop", [-1.0, 1.0, 3.0, 4.0, 3.0, 1.0, 0.0, -1.0, -1.0, -1.0]),
+    ("Jazz", [3.0, 2.0, 1.0, 2.0, -1.0, -1.0, 0.0, 1.0, 2.0, 3.0]),
+    ("Classical", [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -3.0, -3.0, -3.0, -5.0]),
+    ("Electronic", [5.0, 4.0, 1.0, 0.0, -2.0, 2.0, 1.0, 1.0, 4.0, 5.0]),
+    ("Loudness", [5.0, 3.0, 0.0, 0.0, -1.0, 0.0, -1.0, -2.0, 3.0, 2.0]),
+];
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EffectSettings {
+    /// Bypasses the whole chain when off
+    pub enabled: bool,
+    /// Gain of each EQ band, in dB
+    pub bands: [f32; 10],
+    /// Preset the bands came from, "Custom" once one is changed
+    pub preset: String,
+    /// Low shelf gain, in dB
+    pub bass_boost: f32,
+    /// 0 is mono, 1 leaves the stereo image alone, 2 doubles the side signal
+    pub width: f32,
+    pub limiter: bool,
+}
+
+impl Default for EffectSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bands: [0.0; 10],
+            preset: PRESETS[0].0.to_string(),
+            bass_boost: 0.0,
+            width: 1.0,
+            limiter: true,
+        }
+    }
+}
+
+impl EffectSettings {
+    fn path() -> PathBuf {
+        data_dir().join("effects.json")
+    }
+
+    pub fn load() -> EffectSettings {
+        fs::read_to_string(EffectSettings::path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(data_dir())?;
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(EffectSettings::path(), text)
+    }
+}
+
+/// Settings shared between the UI and the sources playing them
+pub struct EffectControls {
+    settings: Mutex<EffectSettings>,
+    /// Bumped on every change, so the audio thread only locks when there's something new
+    version: AtomicU64,
+}
+
+impl EffectControls {
+    pub fn shared(settings: EffectSettings) -> Arc<Self> {
+        Arc::new(Self {
+            settings: Mutex::new(settings),
+            version: AtomicU64::new(0),
+        })
+    }
+
+    pub fn settings(&self) -> EffectSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn update(&self, change: impl FnOnce(&mut EffectSettings)) {
+        change(&mut self.settings.lock().unwrap());
+        self.version.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Second order filter, transposed direct form II
+#[derive(Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    const IDENTITY: Biquad = Biquad { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0, z1: 0.0, z2: 0.0 };
+
+    /// Peaking filter from the RBJ audio EQ cookbook
+    fn peaking(sample_rate: f32, frequency: f32, q: f32, gain: f32) -> Biquad {
+        if gain == 0.0 || frequency >= sample_rate / 2.0 {
+            return Biquad::IDENTITY;
+        }
+        let a = 10f32.powf(gain / 40.0);
+        let w = 2.0 * PI * frequency / sample_rate;
+        let alpha = w.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha / a;
+        Biquad {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * w.cos() / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * w.cos() / a0,
+            a2: (1.0 - alpha / a) / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Low shelf from the RBJ audio EQ cookbook, with a shelf slope of 1
+    fn low_shelf(sample_rate: f32, frequency: f32, gain: f32) -> Biquad {
+        if gain == 0.0 || frequency >= sample_rate / 2.0 {
+            return Biquad::IDENTITY;
+        }
+        let a = 10f32.powf(gain / 40.0);
+        let w = 2.0 * PI * frequency / sample_rate;
+        let alpha = w.sin() / 2.0 * 2f32.sqrt();
+        let cos = w.cos();
+        let root = 2.0 * a.sqrt() * alpha;
+        let a0 = (a + 1.0) + (a - 1.0) * cos + root;
+        Biquad {
+            b0: a * ((a + 1.0) - (a - 1.0) * cos + root) / a0,
+            b1: 2.0 * a * ((a - 1.0) - (a + 1.0) * cos) / a0,
+            b2: a * ((a + 1.0) - (a - 1.0) * cos - root) / a0,
+            a1: -2.0 * ((a - 1.0) + (a + 1.0) * cos) / a0,
+            a2: ((a + 1.0) + (a - 1.0) * cos - root) / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Takes new coefficients but keeps the state, so changing a band doesn't click
+    fn retune(&mut self, other: Biquad) {
+        *self = Biquad { z1: self.z1, z2: self.z2, ..other };
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// EQ, bass boost, stereo width and limiter, in that order
+pub struct Effects<S> {
+    inner: S,
+    controls: Arc<EffectControls>,
+    /// Version of the settings the filters were built from
+    version: Option<u64>,
+    settings: EffectSettings,
+    /// EQ bands then the bass shelf, for each channel
+    filters: Vec<[Biquad; 11]>,
+    limiter_gain: f32,
+    /// Processed frame being handed out
+    frame: Vec<f32>,
+    /// Index of the next sample of `frame`
+    index: usize,
+    /// Frames until the settings are checked again
+    countdown: usize,
+}
+
+impl<S> Effects<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(inner: S, controls: Arc<EffectControls>) -> Self {
+        Self {
+            inner,
+            controls,
+            version: None,
+            settings: EffectSettings::default(),
+            filters: Vec::new(),
+            limiter_gain: 1.0,
+            frame: Vec::new(),
+            index: 0,
+            countdown: 0,
+        }
+    }
+
+    /// Rebuilds the filters if the settings changed since
+    fn refresh(&mut self) {
+        let version = self.controls.version.load(Ordering::Acquire);
+        let channels = self.inner.channels().max(1) as usize;
+        if self.version == Some(version) && self.filters.len() == channels {
+            return;
+        }
+        self.version = Some(version);
+        self.settings = self.controls.settings();
+        let sample_rate = self.inner.sample_rate() as f32;
+        let mut filters = [Biquad::IDENTITY; 11];
+        for (i, (&frequency, &gain)) in BAND_FREQUENCIES.iter().zip(&self.settings.bands).enumerate() {
+            filters[i] = Biquad::peaking(sample_rate, frequency, BAND_Q, gain);
+        }
+        filters[10] = Biquad::low_shelf(sample_rate, BASS_FREQUENCY, self.settings.bass_boost);
+        self.filters.resize(channels, [Biquad::IDENTITY; 11]);
+        for channel in &mut self.filters {
+            for (filter, new) in channel.iter_mut().zip(filters) {
+                filter.retune(new);
+            }
+        }
+    }
+
+    /// Reads and processes the next frame, false at the end of the source
+    fn fill_frame(&mut self) -> bool {
+        let channels = self.inner.channels().max(1) as usize;
+        self.frame.clear();
+        self.index = 0;
+        for _ in 0..channels {
+            match self.inner.next() {
+                Some(sample) => self.frame.push(sample.to_f32()),
+                None => break,
+            }
+        }
+        if self.frame.is_empty() {
+            return false;
+        }
+
+        if self.countdown == 0 {
+            self.refresh();
+            self.countdown = CHECK_FRAMES;
+        }
+        self.countdown -= 1;
+        if !self.settings.enabled {
+            return true;
+        }
+
+        for (sample, filters) in self.frame.iter_mut().zip(&mut self.filters) {
+            for filter in filters.iter_mut() {
+                *sample = filter.process(*sample);
+            }
+        }
+        if self.frame.len() >= 2 && self.settings.width != 1.0 {
+            let mid = (self.frame[0] + self.frame[1]) / 2.0;
+            let side = (self.frame[0] - self.frame[1]) / 2.0 * self.settings.width;
+            self.frame[0] = mid + side;
+            self.frame[1] = mid - side;
+        }
+        if self.settings.limiter {
+            // one gain for the whole frame keeps the stereo image, instant attack and a smooth release
+            let peak = self.frame.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            let needed = if peak > LIMITER_THRESHOLD { LIMITER_THRESHOLD / peak } else { 1.0 };
+            let release = 1.0 / (LIMITER_RELEASE * self.inner.sample_rate().max(1) as f32);
+            self.limiter_gain = needed.min(self.limiter_gain + (1.0 - self.limiter_gain) * release);
+            for sample in &mut self.frame {
+                *sample *= self.limiter_gain;
+            }
+        }
+        true
+    }
+}
+
+impl<S> Iterator for Effects<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.index >= self.frame.len() && !self.fill_frame() {
+            return None;
+        }
+        let sample = self.frame[self.index];
+        self.index += 1;
+        Some(sample)
+    }
+}
+
+impl<S> Source for Effects<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        let buffered = self.frame.len() - self.index;
+        self.inner.current_frame_len().map(|len| len + buffered)
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.frame.clear();
+        self.index = 0;
+        // the filters would ring with whatever they held from before the seek
+        self.filters.clear();
+        self.countdown = 0;
+        Ok(())
+    }
+}
+
+/// Rows of the effects pane
+const ROW_ENABLED: usize = 0;
+const ROW_PRESET: usize = 1;
+const ROW_BANDS: usize = 2;
+const ROW_BASS: usize = 12;
+const ROW_WIDTH: usize = 13;
+const ROW_LIMITER: usize = 14;
+
+/// Selection in the effects pane
+pub struct EffectsPane {
+    selected: usize,
+}
+
+impl EffectsPane {
+    pub fn new() -> Self {
+        Self { selected: ROW_PRESET }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(ROW_LIMITER);
+    }
+
+    /// Moves the selected control down (-1) or up (1)
+    pub fn adjust(&self, settings: &mut EffectSettings, direction: i32) {
+        let step = direction as f32;
+        match self.selected {
+            ROW_ENABLED => {
+                settings.enabled = !settings.enabled;
+            }
+            ROW_PRESET => {
+                let index = PRESETS.iter().position(|(name, _)| *name == settings.preset).unwrap_or(0) as i32;
+                let (name, bands) = PRESETS[(index + direction).rem_euclid(PRESETS.len() as i32) as usize];
+                settings.preset = name.to_string();
+                settings.bands = bands;
+            }
+            ROW_BASS => {
+                settings.bass_boost = (settings.bass_boost + step).clamp(0.0, MAX_GAIN);
+            }
+            ROW_WIDTH => {
+                settings.width = ((settings.width + step * 0.1) * 10.0).round().clamp(0.0, MAX_WIDTH * 10.0) / 10.0;
+            }
+            ROW_LIMITER => {
+                settings.limiter = !settings.limiter;
+            }
+            band => {
+                let gain = &mut settings.bands[band - ROW_BANDS];
+                *gain = (*gain + step).clamp(-MAX_GAIN, MAX_GAIN);
+                settings.preset = "Custom".to_string();
+            }
+        }
+    }
+
+    /// Puts the selected control back to its default
+    pub fn reset(&self, settings: &mut EffectSettings) {
+        let default = EffectSettings::default();
+        match self.selected {
+            ROW_ENABLED => settings.enabled = default.enabled,
+            ROW_PRESET => {
+                settings.preset = default.preset;
+                settings.bands = default.bands;
+            }
+            ROW_BASS => settings.bass_boost = default.bass_boost,
+            ROW_WIDTH => settings.width = default.width,
+            ROW_LIMITER => settings.limiter = default.limiter,
+            band => {
+                settings.bands[band - ROW_BANDS] = 0.0;
+                settings.preset = "Custom".to_string();
+            }
+        }
+    }
+
+    pub fn content(&self, settings: &EffectSettings, width: usize, height: usize) -> Text<'static> {
+        let slider_width = width.saturating_sub(24).clamp(5, 31);
+        let slider = |value: f32, min: f32, max: f32| {
+            let position = ((value - min) / (max - min) * (slider_width - 1) as f32).round() as usize;
+            format!("{}●{}", "─".repeat(position), "─".repeat(slider_width - 1 - position))
+        };
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+
+        let mut rows: Vec<(String, String, String)> = vec![
+            ("effects".to_string(), String::new(), on_off(settings.enabled)),
+            ("preset".to_string(), String::new(), settings.preset.clone()),
+        ];
+        for (frequency, gain) in BAND_FREQUENCIES.iter().zip(&settings.bands) {
+            let name = if *frequency >= 1000.0 { format!("{}k", frequency / 1000.0) } else { format!("{frequency}") };
+            rows.push((format!("{name:>4} Hz"), slider(*gain, -MAX_GAIN, MAX_GAIN), format!("{gain:+.0} dB")));
+        }
+        rows.push(("bass".to_string(), slider(settings.bass_boost, 0.0, MAX_GAIN), format!("{:+.0} dB", settings.bass_boost)));
+        rows.push(("width".to_string(), slider(settings.width, 0.0, MAX_WIDTH), format!("{:.1}", settings.width)));
+        rows.push(("limiter".to_string(), String::new(), on_off(settings.limiter)));
+
+        let mut lines = vec![Line::from(""); height.saturating_sub(rows.len() + 2) / 2];
+        for (i, (name, slider, value)) in rows.into_iter().enumerate() {
+            let marker = if i == self.selected { "> " } else { "  " };
+            let mut name_style = Style::default().fg(palette().text);
+            if i == self.selected {
+                name_style = name_style.bg(palette().dim).add_modifier(Modifier::BOLD);
+            }
+            lines.push(Line::from(vec![
+                Span::styled(marker, Style::default().fg(palette().highlight)),
+                Span::styled(format!("{name:<8}"), name_style),
+                Span::raw(" "),
+                Span::styled(slider, Style::default().fg(palette().special)),
+                Span::raw(" "),
+                Span::styled(value, Style::default().fg(palette().value)),
+            ]));
+            // space between the switches, the bands and the other controls
+            if i == ROW_PRESET || i == ROW_BANDS + 9 {
+                lines.push(Line::from(""));
+            }
+        }
+        Text::from(lines)
+    }
+}
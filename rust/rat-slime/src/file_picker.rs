@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+};
+use crate::config::palette;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum EntryKind {
+    /// ".." entry for the parent directory
+    Parent,
+    Dir,
+    Audio,
+    /// m3u/m3u8 playlist
+    Playlist,
+}
+
+/// A file or directory shown in the picker
+pub struct Entry {
+    pub path: PathBuf,
+    /// Name shown in the list
+    pub name: String,
+    pub kind: EntryKind,
+}
+
+/// Directory browser for choosing folders and tracks
+pub struct FilePicker {
+    /// Directory being shown
+    pub dir: PathBuf,
+    /// Entries of `dir`, parent first, then directories, then playlists and audio files
+    entries: Vec<Entry>,
+    /// Index of the selected entry
+    selected: usize,
+    /// Error from the last directory read, shown instead of the entries
+    error: Option<String>,
+}
+
+impl FilePicker {
+    pub fn new(dir: PathBuf) -> Self {
+        let mut picker = Self {
+            dir,
+            entries: Vec::new(),
+            selected: 0,
+            error: None,
+        };
+        picker.refresh();
+        picker
+    }
+
+    /// Re-reads the current directory
+    pub fn refresh(&mut self) {
+        self.entries.clear();
+        self.error = None;
+        if let Some(parent) = self.dir.parent() {
+            self.entries.push(Entry {
+                path: parent.to_path_buf(),
+                name: "..".to_string(),
+                kind: EntryKind::Parent,
+            });
+        }
+
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(error) => {
+                self.error = Some(error.to_string());
+                return;
+            }
+        };
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                dirs.push(Entry { path, name, kind: EntryKind::Dir });
+            } else if crate::playlist::is_playlist_file(&path) {
+                files.push(Entry { path, name, kind: EntryKind::Playlist });
+            } else if crate::library::is_audio_file(&path) {
+                files.push(Entry { path, name, kind: EntryKind::Audio });
+            }
+        }
+        dirs.sort_by_key(|entry| entry.name.to_lowercase());
+        files.sort_by_key(|entry| entry.name.to_lowercase());
+        self.entries.extend(dirs);
+        self.entries.extend(files);
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    /// What the replace/append actions apply to: the selected directory or playlist, otherwise the directory being shown
+    pub fn selected_target(&self) -> (PathBuf, EntryKind) {
+        match self.selected() {
+            Some(entry) if entry.kind == EntryKind::Dir || entry.kind == EntryKind::Playlist => {
+                (entry.path.clone(), entry.kind)
+            }
+            _ => (self.dir.clone(), EntryKind::Dir),
+        }
+    }
+
+    /// Opens the selected directory, returns the selected file if it isn't one
+    pub fn enter(&mut self) -> Option<PathBuf> {
+        let entry = self.selected()?;
+        match entry.kind {
+            EntryKind::Audio | EntryKind::Playlist => {
+                return Some(entry.path.clone());
+            }
+            EntryKind::Parent => {
+                self.leave();
+            }
+            EntryKind::Dir => {
+                let dir = entry.path.clone();
+                self.open(dir);
+            }
+        }
+        None
+    }
+
+    /// Shows another directory
+    pub fn open(&mut self, dir: PathBuf) {
+        self.dir = dir;
+        self.selected = 0;
+        self.refresh();
+    }
+
+    /// Goes to the parent directory, keeping the directory we came from selected
+    pub fn leave(&mut self) {
+        let Some(parent) = self.dir.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        let previous = std::mem::replace(&mut self.dir, parent);
+        self.selected = 0;
+        self.refresh();
+        if let Some(index) = self.entries.iter().position(|entry| entry.path == previous) {
+            self.selected = index;
+        }
+    }
+
+    pub fn content(&self, width: usize, height: usize, focused: bool) -> Text<'static> {
+        let mut lines = Vec::new();
+        let mut dir = self.dir.to_string_lossy().to_string();
+        if dir.chars().count() > width.saturating_sub(2) {
+            let keep = width.saturating_sub(3);
+            dir = format!("~{}", dir.chars().skip(dir.chars().count() - keep).collect::<String>());
+        }
+        lines.push(Line::from(Span::styled(format!(" {dir}"), Style::default().fg(palette().accent))));
+
+        if let Some(error) = &self.error {
+            lines.push(Line::from(Span::styled(format!(" {error}"), Style::default().fg(palette().error))));
+            return Text::from(lines);
+        }
+
+        // keep the selection in view
+        let rows = height.saturating_sub(1).max(1);
+        let start = self.selected.saturating_sub(rows / 2).min(self.entries.len().saturating_sub(rows));
+        for (i, entry) in self.entries.iter().enumerate().skip(start).take(rows) {
+            let (name, color) = match entry.kind {
+                EntryKind::Parent | EntryKind::Dir => (format!("{}/", entry.name), palette().title),
+                EntryKind::Playlist => (entry.name.clone(), palette().special),
+                EntryKind::Audio => (entry.name.clone(), palette().text),
+            };
+            let mut style = Style::default().fg(color);
+            if i == self.selected && focused {
+                style = style.bg(palette().dim).add_modifier(Modifier::BOLD);
+            }
+            let marker = if i == self.selected { "> " } else { "  " };
+            lines.push(Line::from(vec![
+                Span::styled(marker, Style::default().fg(palette().highlight)),
+                Span::styled(name, style),
+            ]));
+        }
+        Text::from(lines)
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use crate::{data_dir, Song};
+
+/// Extensions of the formats rodio is built to decode
+const AUDIO_EXTENSIONS: [&str; 9] = ["mp3", "flac", "ogg", "oga", "wav", "m4a", "m4b", "mp4", "aac"];
+
+/// A file that looked like audio but couldn't be loaded
+pub struct ScanError {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Whether a file looks like something the player can decode, by extension or by its first bytes
+pub fn is_audio_file(path: &Path) -> bool {
+    let extension = path.extension().and_then(|s| s.to_str()).map(str::to_lowercase);
+    match extension {
+        Some(extension) if AUDIO_EXTENSIONS.contains(&extension.as_str()) => true,
+        _ => sniff(path),
+    }
+}
+
+/// Checks the magic bytes of the containers we can decode
+fn sniff(path: &Path) -> bool {
+    let mut header = [0u8; 12];
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    if file.read_exact(&mut header).is_err() {
+        return false;
+    }
+    header.starts_with(b"ID3") // mp3 with id3v2 tag
+        || header.starts_with(b"fLaC")
+        || header.starts_with(b"OggS")
+        || (header.starts_with(b"RIFF") && &header[8..12] == b"WAVE")
+        || &header[4..8] == b"ftyp" // mp4/m4a
+        || (header[0] == 0xFF && header[1] & 0xE0 == 0xE0) // mpeg/adts frame sync
+}
+
+/// Finds every audio file in a folder, and in its subfolders when `recursive` is set
+pub fn find_audio_files(folder: &Path, recursive: bool) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![folder.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(read_dir) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            // file_type doesn't follow symlinks, so linked folders can't loop the scan
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if recursive {
+                    dirs.push(path);
+                }
+            } else if is_audio_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+    // path order keeps albums together and tracks in filename order
+    files.sort();
+    files
+}
+
+/// A file as it was when it was last read
+#[derive(Serialize, Deserialize)]
+struct Indexed {
+    modified: SystemTime,
+    /// The song, or why it couldn't be loaded, so broken files aren't decoded again every time either
+    song: Result<Song, String>,
+}
+
+/// Tags and durations of every file scanned so far, kept between runs so only new or changed files are read again
+#[derive(Serialize, Deserialize, Default)]
+pub struct Library {
+    files: HashMap<PathBuf, Indexed>,
+    /// Whether anything changed since the index was last saved
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl Library {
+    fn path() -> PathBuf {
+        data_dir().join("library.json")
+    }
+
+    /// Opens the saved index, an unreadable one is just rebuilt
+    pub fn open() -> Library {
+        fs::read_to_string(Library::path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&mut self) {
+        if !self.changed {
+            return;
+        }
+        // failing to save only means reading the tags again next time
+        let saved = fs::create_dir_all(data_dir())
+            .and_then(|_| serde_json::to_string(self).map_err(io::Error::from))
+            .and_then(|text| fs::write(Library::path(), text));
+        self.changed = saved.is_err();
+    }
+
+    /// Loads a song from the index, reading the file again only if it changed since
+    pub fn song(&mut self, path: &Path) -> Result<Song, String> {
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|error| error.to_string())?;
+        if let Some(indexed) = self.files.get(path) {
+            if indexed.modified == modified {
+                return indexed.song.clone();
+            }
+        }
+        let song = Song::new(path.to_path_buf()).map_err(|error| error.to_string());
+        self.files.insert(path.to_path_buf(), Indexed { modified, song: song.clone() });
+        self.changed = true;
+        song
+    }
+
+    /// Loads every song in a folder, collecting the files that fail instead of stopping at them
+    pub fn scan(&mut self, folder: &Path, recursive: bool) -> (Vec<Song>, Vec<ScanError>) {
+        let paths = find_audio_files(folder, recursive);
+        // files that went away since the last scan of this folder
+        let found: HashSet<&PathBuf> = paths.iter().collect();
+        let before = self.files.len();
+        self.files.retain(|path, _| {
+            let scanned = if recursive { path.starts_with(folder) } else { path.parent() == Some(folder) };
+            !scanned || found.contains(path)
+        });
+        self.changed |= self.files.len() != before;
+        self.load(paths)
+    }
+
+    /// Loads a list of files, collecting the ones that fail instead of stopping at them
+    pub fn load(&mut self, paths: Vec<PathBuf>) -> (Vec<Song>, Vec<ScanError>) {
+        let mut songs = Vec::new();
+        let mut errors = Vec::new();
+        for path in paths {
+            match self.song(&path) {
+                Ok(song) => songs.push(song),
+                Err(error) => errors.push(ScanError { path, error }),
+            }
+        }
+        self.save();
+        (songs, errors)
+    }
+
+    /// Every song in the index, in path order
+    pub fn songs(&self) -> Vec<&Song> {
+        let mut songs: Vec<&Song> = self.files.values().filter_map(|indexed| indexed.song.as_ref().ok()).collect();
+        songs.sort_by(|a, b| a.path.cmp(&b.path));
+        songs
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+};
+use crate::config::palette;
+use crate::library::Library;
+use crate::Song;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum View {
+    Songs,
+    Artists,
+    Albums,
+}
+
+impl View {
+    const ALL: [View; 3] = [View::Songs, View::Artists, View::Albums];
+
+    fn name(&self) -> &'static str {
+        match self {
+            View::Songs => "Songs",
+            View::Artists => "Artists",
+            View::Albums => "Albums",
+        }
+    }
+}
+
+/// A row of the list: a song, or an artist/album with its songs
+pub enum Item {
+    Song(Song),
+    Group { name: String, songs: Vec<Song> },
+}
+
+impl Item {
+    pub fn songs(&self) -> Vec<Song> {
+        match self {
+            Item::Song(song) => vec![song.clone()],
+            Item::Group { songs, .. } => songs.clone(),
+        }
+    }
+}
+
+/// Search and browse views over the library index
+pub struct LibraryView {
+    pub view: View,
+    /// Fuzzy filter, matched against title, artist and album
+    pub query: String,
+    /// Artist or album opened from a browse view, its songs are listed instead
+    group: Option<(String, Vec<Song>)>,
+    /// Rows matching the query, best match first
+    items: Vec<Item>,
+    /// Index of the selected row
+    selected: usize,
+}
+
+impl LibraryView {
+    pub fn new() -> Self {
+        Self {
+            view: View::Songs,
+            query: String::new(),
+            group: None,
+            items: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    /// Rebuilds the rows from the library and the query
+    pub fn refresh(&mut self, library: &Library) {
+        let matcher = SkimMatcherV2::default();
+        let score = |text: &str| {
+            if self.query.is_empty() {
+                Some(0)
+            } else {
+                matcher.fuzzy_match(text, &self.query)
+            }
+        };
+
+        let mut scored: Vec<(i64, Item)> = match (&self.group, self.view) {
+            (Some((_, songs)), _) => songs
+                .iter()
+                .filter_map(|song| Some((score(&search_text(song))?, Item::Song(song.clone()))))
+                .collect(),
+            (None, View::Songs) => library
+                .songs()
+                .into_iter()
+                .filter_map(|song| Some((score(&search_text(song))?, Item::Song(song.clone()))))
+                .collect(),
+            (None, view) => {
+                let mut groups: BTreeMap<String, Vec<Song>> = BTreeMap::new();
+                for song in library.songs() {
+                    let artist = song.get_artist().unwrap_or("Unknown artist");
+                    let name = match view {
+                        View::Albums => format!("{} - {}", song.get_album().unwrap_or("Unknown album"), artist),
+                        _ => artist.to_string(),
+                    };
+                    groups.entry(name).or_default().push(song.clone());
+                }
+                groups
+                    .into_iter()
+                    .filter_map(|(name, songs)| Some((score(&name)?, Item::Group { name, songs })))
+                    .collect()
+            }
+        };
+        // stable, so equal scores (and everything when there's no query) keep the library order
+        scored.sort_by_key(|(score, _)| -score);
+        self.items = scored.into_iter().map(|(_, item)| item).collect();
+        self.selected = self.selected.min(self.items.len().saturating_sub(1));
+    }
+
+    /// Switches to the next (1) or previous (-1) view
+    pub fn cycle_view(&mut self, direction: i32, library: &Library) {
+        let index = View::ALL.iter().position(|&view| view == self.view).unwrap_or(0) as i32;
+        let index = (index + direction).rem_euclid(View::ALL.len() as i32);
+        self.view = View::ALL[index as usize];
+        self.group = None;
+        self.selected = 0;
+        self.refresh(library);
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.items.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Item> {
+        self.items.get(self.selected)
+    }
+
+    /// Every song in the rows matching the query
+    pub fn all_songs(&self) -> Vec<Song> {
+        self.items.iter().flat_map(Item::songs).collect()
+    }
+
+    /// Lists the songs of the selected artist/album, the query starts over
+    pub fn open_group(&mut self, library: &Library) {
+        if let Some(Item::Group { name, songs }) = self.selected() {
+            self.group = Some((name.clone(), songs.clone()));
+            self.query.clear();
+            self.selected = 0;
+            self.refresh(library);
+        }
+    }
+
+    /// Goes back from an artist/album to the list of them, returns false if none was open
+    pub fn close_group(&mut self, library: &Library) -> bool {
+        if self.group.take().is_none() {
+            return false;
+        }
+        self.query.clear();
+        self.selected = 0;
+        self.refresh(library);
+        true
+    }
+
+    pub fn content(&self, width: usize, height: usize) -> Text<'static> {
+        let mut lines = Vec::new();
+
+        let mut tabs = vec![Span::raw(" ")];
+        for view in View::ALL {
+            let style = if view == self.view {
+                Style::default().fg(palette().highlight).add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            } else {
+                Style::default().fg(palette().dim)
+            };
+            tabs.push(Span::styled(view.name(), style));
+            tabs.push(Span::raw("   "));
+        }
+        if let Some((name, _)) = &self.group {
+            tabs.push(Span::styled(format!("> {name}"), Style::default().fg(palette().title)));
+        }
+        lines.push(Line::from(tabs));
+        lines.push(Line::from(vec![
+            Span::styled(" search: ", Style::default().fg(palette().accent)),
+            Span::styled(self.query.clone(), Style::default().fg(palette().value)),
+            Span::styled("▏", Style::default().fg(palette().highlight)),
+            Span::styled(format!("  {} result(s)", self.items.len()), Style::default().fg(palette().dim)),
+        ]));
+        lines.push(Line::from(""));
+
+        // keep the selection in view
+        let rows = height.saturating_sub(lines.len()).max(1);
+        let start = self.selected.saturating_sub(rows / 2).min(self.items.len().saturating_sub(rows));
+        for (i, item) in self.items.iter().enumerate().skip(start).take(rows) {
+            let marker = if i == self.selected { "> " } else { "  " };
+            let mut spans = vec![Span::styled(marker, Style::default().fg(palette().highlight))];
+            match item {
+                Item::Song(song) => {
+                    spans.push(Span::styled(song.get_title().unwrap_or("--").to_string(), Style::default().fg(palette().title)));
+                    spans.push(Span::styled(" - ", Style::default().fg(palette().text)));
+                    spans.push(Span::styled(song.get_artist().unwrap_or("--").to_string(), Style::default().fg(palette().highlight)));
+                    spans.push(Span::styled(
+                        format!("  ({})", song.get_album().unwrap_or("--")),
+                        Style::default().fg(palette().dim),
+                    ));
+                }
+                Item::Group { name, songs } => {
+                    spans.push(Span::styled(name.clone(), Style::default().fg(palette().title)));
+                    spans.push(Span::styled(format!("  ({})", songs.len()), Style::default().fg(palette().dim)));
+                }
+            }
+            if i == self.selected {
+                for span in spans.iter_mut().skip(1) {
+                    span.style = span.style.bg(palette().dim).add_modifier(Modifier::BOLD);
+                }
+            }
+            lines.push(Line::from(spans));
+        }
+        if self.items.is_empty() {
+            lines.push(Line::from(Span::styled(
+                format!("{:pad$}nothing found", "", pad = width.saturating_sub(13) / 2),
+                Style::default().fg(palette().dim),
+            )));
+        }
+        Text::from(lines)
+    }
+}
+
+/// What the query is matched against for a song
+fn search_text(song: &Song) -> String {
+    format!(
+        "{} {} {}",
+        song.get_title().unwrap_or(""),
+        song.get_artist().unwrap_or(""),
+        song.get_album().unwrap_or(""),
+    )
+}
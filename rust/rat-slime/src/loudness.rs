@@ -0,0 +1,440 @@
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use lofty::file::TaggedFileExt;
+use lofty::tag::ItemKey;
+use rodio::source::SeekError;
+use rodio::{Decoder, Sample, Source};
+use serde::{Deserialize, Serialize};
+use crate::{data_dir, Song};
+
+/// Loudness everything is brought to, the ReplayGain 2.0 reference
+const REFERENCE_LUFS: f64 = -18.0;
+/// Seconds a gain change is spread over, so a measurement finishing mid-song doesn't jump
+const GAIN_RAMP_SECONDS: f32 = 0.5;
+
+/// Which ReplayGain a song is played at
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GainMode {
+    Off,
+    /// Every song at the same loudness
+    #[default]
+    Track,
+    /// Songs at the loudness of their album, keeping the differences between its tracks
+    Album,
+}
+
+impl GainMode {
+    pub fn next(&self) -> GainMode {
+        match self {
+            GainMode::Off => GainMode::Track,
+            GainMode::Track => GainMode::Album,
+            GainMode::Album => GainMode::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GainMode::Off => "off",
+            GainMode::Track => "track",
+            GainMode::Album => "album",
+        }
+    }
+}
+
+/// Gain of a source that's already in the sink, so it can still change once its loudness is known
+#[derive(Clone)]
+pub struct GainHandle(Arc<AtomicU32>);
+
+impl GainHandle {
+    pub fn new(gain: f32) -> Self {
+        Self(Arc::new(AtomicU32::new(gain.to_bits())))
+    }
+
+    pub fn set(&self, gain: f32) {
+        self.0.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Source played at the gain of its GainHandle, moving to a new gain smoothly
+pub struct Gain<S> {
+    inner: S,
+    handle: GainHandle,
+    /// Gain being applied, following the handle
+    current: f32,
+    /// How far `current` moves towards the handle's gain each sample
+    step: f32,
+}
+
+impl<S> Gain<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(inner: S, handle: GainHandle) -> Self {
+        let samples = inner.sample_rate() as f32 * inner.channels() as f32 * GAIN_RAMP_SECONDS;
+        Self { current: handle.get(), step: 1.0 / samples.max(1.0), inner, handle }
+    }
+}
+
+impl<S> Iterator for Gain<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let target = self.handle.get();
+        if self.current != target {
+            let step = self.step * target.max(self.current);
+            self.current = if target > self.current {
+                (self.current + step).min(target)
+            } else {
+                (self.current - step).max(target)
+            };
+        }
+        Some(self.inner.next()?.to_f32() * self.current)
+    }
+}
+
+impl<S> Source for Gain<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
+
+/// ReplayGain from a song's tags, in dB, with the peaks as linear sample values
+#[derive(Clone, Copy, Default)]
+struct Tags {
+    track_gain: Option<f64>,
+    track_peak: Option<f64>,
+    album_gain: Option<f64>,
+    album_peak: Option<f64>,
+}
+
+impl Tags {
+    fn read(path: &Path) -> Tags {
+        let Ok(tagged_file) = lofty::read_from_path(path) else {
+            return Tags::default();
+        };
+        // "-6.54 dB", "0.988553"
+        let value = |key: ItemKey| {
+            tagged_file.tags().iter().find_map(|tag| {
+                let text = tag.get_string(&key)?;
+                text.trim().trim_end_matches("dB").trim_end_matches("db").trim().parse::<f64>().ok()
+            })
+        };
+        Tags {
+            track_gain: value(ItemKey::ReplayGainTrackGain),
+            track_peak: value(ItemKey::ReplayGainTrackPeak),
+            album_gain: value(ItemKey::ReplayGainAlbumGain),
+            album_peak: value(ItemKey::ReplayGainAlbumPeak),
+        }
+    }
+}
+
+/// Loudness of a file measured by the player, for songs without ReplayGain tags
+#[derive(Clone, Serialize, Deserialize)]
+struct Measured {
+    modified: SystemTime,
+    /// Integrated loudness, in LUFS
+    loudness: f64,
+    /// Highest sample, linear
+    peak: f64,
+    /// Seconds of audio, for weighing tracks when working out the album's loudness
+    duration: f64,
+}
+
+/// ReplayGain from tags, falling back to loudness measured in the background and cached between runs
+pub struct Loudness {
+    measured: HashMap<PathBuf, Measured>,
+    /// Tags read so far this run
+    tags: HashMap<PathBuf, Tags>,
+    /// Files sent to be measured, failed ones stay here so they aren't tried again
+    pending: HashSet<PathBuf>,
+    /// Files for the background thread to measure
+    requests: Sender<PathBuf>,
+    results: Receiver<(PathBuf, Option<Measured>)>,
+}
+
+impl Loudness {
+    fn path() -> PathBuf {
+        data_dir().join("loudness.json")
+    }
+
+    /// Opens the cache and starts the thread measuring files
+    pub fn open() -> Loudness {
+        let measured = fs::read_to_string(Loudness::path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        let (requests, receiver) = mpsc::channel::<PathBuf>();
+        let (sender, results) = mpsc::channel();
+        thread::spawn(move || {
+            for path in receiver {
+                let measured = measure(&path).ok().flatten();
+                if sender.send((path, measured)).is_err() {
+                    return;
+                }
+            }
+        });
+        Loudness { measured, tags: HashMap::new(), pending: HashSet::new(), requests, results }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(data_dir())?;
+        fs::write(Loudness::path(), serde_json::to_string(&self.measured)?)
+    }
+
+    /// Takes in finished measurements, returns whether there were any
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        for (path, measured) in self.results.try_iter() {
+            if let Some(measured) = measured {
+                self.pending.remove(&path);
+                self.measured.insert(path, measured);
+                changed = true;
+            }
+        }
+        if changed {
+            // only means measuring again next time
+            let _ = self.save();
+        }
+        changed
+    }
+
+    fn tags(&mut self, path: &Path) -> Tags {
+        *self.tags.entry(path.to_path_buf()).or_insert_with(|| Tags::read(path))
+    }
+
+    /// Cached measurement, if it's still of the file as it is
+    fn measured(&self, path: &Path) -> Option<&Measured> {
+        let measured = self.measured.get(path)?;
+        (Some(measured.modified) == modified(path)).then_some(measured)
+    }
+
+    /// Has a song measured in the background, unless it's tagged, measured or already on its way
+    pub fn request(&mut self, song: &Song) {
+        let tags = self.tags(&song.path);
+        if tags.track_gain.is_some() || self.measured(&song.path).is_some() || self.pending.contains(&song.path) {
+            return;
+        }
+        self.pending.insert(song.path.clone());
+        let _ = self.requests.send(song.path.clone());
+    }
+
+    /// Linear gain to play a song at, `album` being the other songs of its album
+    pub fn gain(&mut self, song: &Song, album: &[&Song], mode: GainMode) -> f32 {
+        if mode == GainMode::Off {
+            return 1.0;
+        }
+        let tags = self.tags(&song.path);
+        let tagged = match mode {
+            GainMode::Album => tags.album_gain.map(|gain| (gain, tags.album_peak)).or(tags.track_gain.map(|gain| (gain, tags.track_peak))),
+            _ => tags.track_gain.map(|gain| (gain, tags.track_peak)),
+        };
+        let (gain, peak) = match tagged {
+            Some(tagged) => tagged,
+            None => {
+                self.request(song);
+                let computed = match mode {
+                    GainMode::Album => self.album_gain(song, album),
+                    _ => None,
+                };
+                let track = || self.measured(&song.path).map(|measured| (REFERENCE_LUFS - measured.loudness, Some(measured.peak)));
+                match computed.or_else(track) {
+                    Some(computed) => computed,
+                    // played as it is until the measurement is done
+                    None => return 1.0,
+                }
+            }
+        };
+        let gain = 10f64.powf(gain / 20.0);
+        // never louder than the peak allows
+        let gain = match peak {
+            Some(peak) if peak > 0.0 => gain.min(1.0 / peak),
+            _ => gain,
+        };
+        gain as f32
+    }
+
+    /// Gain from the loudness of a whole album, once every song of it has been measured
+    fn album_gain(&mut self, song: &Song, album: &[&Song]) -> Option<(f64, Option<f64>)> {
+        for other in album {
+            self.request(other);
+        }
+        let mut power = 0.0;
+        let mut duration = 0.0;
+        let mut peak: f64 = 0.0;
+        for path in album.iter().map(|other| &other.path).chain([&song.path]) {
+            let measured = self.measured(path)?;
+            // the loudness of the tracks put together, weighted by how long they are
+            power += 10f64.powf(measured.loudness / 10.0) * measured.duration;
+            duration += measured.duration;
+            peak = peak.max(measured.peak);
+        }
+        if duration <= 0.0 {
+            return None;
+        }
+        Some((REFERENCE_LUFS - 10.0 * (power / duration).log10(), Some(peak)))
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Integrated loudness, sample peak and duration of a file
+fn measure(path: &Path) -> Result<Option<Measured>, Box<dyn std::error::Error>> {
+    let modified = modified(path).unwrap_or(SystemTime::UNIX_EPOCH);
+    let decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+    let channels = decoder.channels().max(1) as usize;
+    let sample_rate = decoder.sample_rate() as f64;
+    let measured = integrated_loudness(decoder.map(|sample| sample.to_f32()), channels, sample_rate);
+    Ok(measured.map(|measured| Measured { modified, ..measured }))
+}
+
+/// Integrated loudness (EBU R128 / ITU-R BS.1770) of interleaved samples, with their sample peak and duration
+///
+/// None when there's too little audio, or it's too quiet, to have a loudness. The modified time is left for the caller.
+fn integrated_loudness(samples: impl Iterator<Item = f32>, channels: usize, sample_rate: f64) -> Option<Measured> {
+    // left, right and centre count once, surrounds more, the LFE not at all
+    let weights: Vec<f64> = match channels {
+        6 => vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+        _ => vec![1.0; channels],
+    };
+
+    let mut filters: Vec<KWeighting> = (0..channels).map(|_| KWeighting::new(sample_rate)).collect();
+    // mean squares of 100ms steps, four of them make a 400ms gating block
+    let step_frames = (sample_rate / 10.0) as usize;
+    let mut steps: Vec<f64> = Vec::new();
+    let mut sum = 0.0;
+    let mut frames = 0usize;
+    let mut total_frames = 0usize;
+    let mut peak: f64 = 0.0;
+    let mut channel = 0;
+    for sample in samples {
+        let sample = sample as f64;
+        peak = peak.max(sample.abs());
+        let filtered = filters[channel].process(sample);
+        sum += weights[channel] * filtered * filtered;
+        channel += 1;
+        if channel == channels {
+            channel = 0;
+            frames += 1;
+            total_frames += 1;
+            if frames == step_frames {
+                steps.push(sum / step_frames as f64);
+                sum = 0.0;
+                frames = 0;
+            }
+        }
+    }
+
+    let blocks: Vec<f64> = steps.windows(4).map(|window| window.iter().sum::<f64>() / 4.0).collect();
+    let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+    let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+    // absolute gate at -70 LUFS, then a relative one 10 LU under what's left
+    let audible: Vec<f64> = blocks.into_iter().filter(|&power| loudness(power) > -70.0).collect();
+    if audible.is_empty() {
+        return None;
+    }
+    let threshold = loudness(mean(&audible)) - 10.0;
+    let gated: Vec<f64> = audible.into_iter().filter(|&power| loudness(power) > threshold).collect();
+    if gated.is_empty() {
+        return None;
+    }
+    Some(Measured {
+        modified: SystemTime::UNIX_EPOCH,
+        loudness: loudness(mean(&gated)),
+        peak,
+        duration: total_frames as f64 / sample_rate,
+    })
+}
+
+/// The K-weighting of BS.1770, a high shelf for the head followed by a high pass
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    /// Coefficients worked out for any sample rate, they match the ones BS.1770 lists for 48kHz
+    fn new(sample_rate: f64) -> Self {
+        let k = (PI * 1681.974450955533 / sample_rate).tan();
+        let q = 0.7071752369554196;
+        let vh = 10f64.powf(3.999843853973347 / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        };
+
+        let k = (PI * 38.13547087602444 / sample_rate).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        };
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use id3::frame::TimestampFormat;
+use lofty::file::TaggedFileExt;
+use lofty::tag::ItemKey;
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+};
+use crate::config::palette;
+use crate::Song;
+
+/// Samples in an mpeg audio frame, for SYLT timestamps counted in frames
+const MPEG_FRAME_SAMPLES: f64 = 1152.0;
+
+struct LyricLine {
+    /// Seconds into the song the line is sung at, None for untimed lyrics
+    time: Option<f64>,
+    text: String,
+}
+
+/// Lyrics of the current song, from a .lrc next to it or from its tags
+pub struct Lyrics {
+    /// Track the lyrics were loaded for
+    song: Option<PathBuf>,
+    /// Sorted by time when they're synced
+    lines: Vec<LyricLine>,
+    /// Where the lyrics came from, shown in the title
+    pub source: &'static str,
+}
+
+impl Lyrics {
+    pub fn new() -> Self {
+        Self {
+            song: None,
+            lines: Vec::new(),
+            source: "",
+        }
+    }
+
+    /// Loads the lyrics when the song changes
+    pub fn update(&mut self, song: Option<&Song>) {
+        if self.song.as_ref() == song.map(|song| &song.path) {
+            return;
+        }
+        self.song = song.map(|song| song.path.clone());
+        (self.lines, self.source) = song.and_then(load).unwrap_or_default();
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.lines.iter().any(|line| line.time.is_some())
+    }
+
+    /// Index of the line being sung at a position
+    fn current(&self, position: f64) -> Option<usize> {
+        self.lines.iter().rposition(|line| line.time.is_some_and(|time| time <= position))
+    }
+
+    /// Lyrics around the current line, or scrolled along with the song when they aren't synced
+    pub fn content(&self, position: f64, duration: f64, width: usize, height: usize) -> Text<'static> {
+        if self.lines.is_empty() {
+            let mut lines = vec![Line::from(""); height.saturating_sub(1) / 2];
+            lines.push(Line::from(Span::styled("no lyrics", Style::default().fg(palette().dim))));
+            return Text::from(lines);
+        }
+
+        let current = self.current(position);
+        let start = if self.is_synced() {
+            // keep the current line in the middle
+            current.unwrap_or(0).saturating_sub(height / 2)
+        } else {
+            let fraction = if duration > 0.0 { (position / duration).clamp(0.0, 1.0) } else { 0.0 };
+            (fraction * self.lines.len().saturating_sub(height) as f64) as usize
+        };
+
+        let mut lines = Vec::new();
+        for (i, line) in self.lines.iter().enumerate().skip(start).take(height) {
+            let text: String = line.text.chars().take(width).collect();
+            let style = match current {
+                Some(current) if i == current => Style::default().fg(palette().highlight).add_modifier(Modifier::BOLD),
+                Some(current) if i < current => Style::default().fg(palette().dim),
+                _ => Style::default().fg(palette().text),
+            };
+            lines.push(Line::from(Span::styled(text, style)));
+        }
+        Text::from(lines)
+    }
+}
+
+/// A .lrc next to the track, then synced id3 lyrics, then unsynced lyrics from the tags
+fn load(song: &Song) -> Option<(Vec<LyricLine>, &'static str)> {
+    if let Some(text) = sidecar(&song.path) {
+        return Some((parse_lrc(&text), "lrc"));
+    }
+    if let Ok(tag) = id3::Tag::read_from_path(&song.path) {
+        if let Some(sylt) = tag.synchronised_lyrics().next() {
+            return Some((parse_sylt(sylt, song.sample_rate), "SYLT"));
+        }
+        if let Some(uslt) = tag.lyrics().next() {
+            return Some((parse_lrc(&uslt.text), "USLT"));
+        }
+    }
+    // vorbis comments, mp4 atoms, ...
+    let tagged_file = lofty::read_from_path(&song.path).ok()?;
+    let text = tagged_file.tags().iter().find_map(|tag| tag.get_string(&ItemKey::Lyrics))?;
+    Some((parse_lrc(text), "tags"))
+}
+
+/// Contents of the .lrc with the same name as the track
+fn sidecar(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?;
+    for entry in fs::read_dir(path.parent()?).ok()?.flatten() {
+        let file = entry.path();
+        let is_lrc = file.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("lrc"));
+        if is_lrc && file.file_stem() == Some(stem) {
+            let bytes = fs::read(&file).ok()?;
+            return Some(String::from_utf8_lossy(&bytes).trim_start_matches('\u{feff}').to_string());
+        }
+    }
+    None
+}
+
+/// Parses lrc lyrics, text without any timestamps comes out as untimed lines
+fn parse_lrc(text: &str) -> Vec<LyricLine> {
+    let mut timed = Vec::new();
+    let mut untimed = Vec::new();
+    // [offset:+500] shows the lyrics half a second sooner
+    let mut offset = 0.0;
+
+    for raw in text.lines() {
+        let mut rest = raw.trim();
+        let mut times = Vec::new();
+        let mut tagged = false;
+        // every [..] at the start of the line, but not things like [Chorus]
+        while let Some(end) = rest.strip_prefix('[').and_then(|tag| tag.find(']')) {
+            let tag = &rest[1..end + 1];
+            if let Some(time) = parse_timestamp(tag) {
+                times.push(time);
+            } else if let Some(value) = tag.strip_prefix("offset:") {
+                offset = value.trim().parse::<f64>().unwrap_or(0.0) / 1000.0;
+            } else if !tag.contains(':') {
+                break;
+            }
+            tagged = true;
+            rest = &rest[end + 2..];
+        }
+        let line = strip_word_timestamps(rest);
+        if !times.is_empty() {
+            for time in times {
+                timed.push(LyricLine { time: Some(time), text: line.clone() });
+            }
+        } else if !tagged {
+            untimed.push(LyricLine { time: None, text: line });
+        }
+    }
+
+    if timed.is_empty() {
+        return untimed;
+    }
+    for line in &mut timed {
+        line.time = line.time.map(|time| (time - offset).max(0.0));
+    }
+    timed.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+    timed
+}
+
+/// mm:ss, mm:ss.xx or mm:ss:xx to seconds
+fn parse_timestamp(tag: &str) -> Option<f64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    if minutes.is_empty() || !minutes.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let seconds = seconds.replacen(':', ".", 1);
+    if seconds.is_empty() || !seconds.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    Some(minutes.parse::<f64>().ok()? * 60.0 + seconds.parse::<f64>().ok()?)
+}
+
+/// Removes the <mm:ss.xx> word timings of enhanced lrc
+fn strip_word_timestamps(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        result += &rest[..start];
+        if parse_timestamp(&rest[start + 1..start + end]).is_none() {
+            result += &rest[start..start + end + 1];
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result += rest;
+    result.trim().to_string()
+}
+
+/// SYLT frames hold either a line per entry, or a syllable per entry with a newline starting each line
+fn parse_sylt(sylt: &id3::frame::SynchronisedLyrics, sample_rate: u32) -> Vec<LyricLine> {
+    let seconds = |timestamp: u32| match sylt.timestamp_format {
+        TimestampFormat::Ms => timestamp as f64 / 1000.0,
+        TimestampFormat::Mpeg => timestamp as f64 * MPEG_FRAME_SAMPLES / sample_rate.max(1) as f64,
+    };
+    let syllables = sylt.content.iter().any(|(_, text)| text.starts_with(['\n', '\r']));
+
+    let mut lines: Vec<LyricLine> = Vec::new();
+    for (timestamp, text) in &sylt.content {
+        match lines.last_mut() {
+            Some(line) if syllables && !text.starts_with(['\n', '\r']) => {
+                line.text += text;
+            }
+            _ => {
+                lines.push(LyricLine { time: Some(seconds(*timestamp)), text: text.trim().to_string() });
+            }
+        }
+    }
+    for line in &mut lines {
+        line.text = line.text.trim().to_string();
+    }
+    lines.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+    lines
+}
@@ -1,11 +1,27 @@
-use std::collections::{VecDeque};
+mod config;
+mod control;
+mod cover;
+mod effects;
+mod file_picker;
+mod library;
+mod library_view;
+mod loudness;
+mod lyrics;
+mod playlist;
+mod plays;
+mod stats_view;
+mod stretch;
+mod transition;
+mod visualizer;
+
+use std::collections::{HashMap, VecDeque};
 use color_eyre::Result;
 use ratatui::{
     crossterm::event::{self, Event, KeyCode, KeyEventKind},
     layout::{Constraint, Layout},
-    style::{Color, Style, Stylize},
+    style::{Modifier, Style, Stylize},
     text::{Line, Span, Text},
-    widgets::{Block, Paragraph, Borders},
+    widgets::{Block, Clear, Paragraph, Borders},
     DefaultTerminal, Frame,
 };
 use std::fs::File;
@@ -13,25 +29,50 @@ use std::{fs, io};
 use std::error::Error;
 use std::io::BufReader;
 use std::time::{Duration, Instant};
-use crossterm::event::KeyEvent;
-use ratatui::layout::{Alignment, Rect};
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::{Alignment, Position, Rect};
 use rodio::{Decoder, OutputStream, Sink};
 use rodio::source::{Source};
 use id3::{TagLike};
 use lofty::file::{AudioFile, TaggedFileExt};
 use lofty::prelude::Accessor;
 use lofty::read_from_path;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use config::{palette, Action, Context, Keymap};
+use control::{Command, Request, Server};
+use cover::Cover;
+use effects::{EffectControls, EffectSettings, Effects, EffectsPane};
+use file_picker::{EntryKind, FilePicker};
+use library::{Library, ScanError};
+use library_view::{Item, LibraryView};
+use loudness::{Gain, GainHandle, GainMode, Loudness};
+use lyrics::Lyrics;
+use playlist::Session;
+use plays::{Listening, PlayLog};
+use stats_view::StatsView;
+use stretch::{Stretch, Tempo};
+use transition::{Crossfade, Cut, CutHandle};
+use visualizer::{SampleBuffer, Tap, Visualizer};
 
 fn main() -> Result<()> {
     color_eyre::install()?;
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("ctl") {
+        return control::client(&args[2..]);
+    }
     let terminal = ratatui::init();
+    crossterm::execute!(io::stdout(), EnableMouseCapture)?;
     let result = App::new().run(terminal);
+    crossterm::execute!(io::stdout(), DisableMouseCapture)?;
     ratatui::restore();
     Ok(result?)
 }
 
 /// struct to hold information for a song/audio
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 struct Song {
     /// Path to the audio file
     path: std::path::PathBuf,
@@ -49,40 +90,45 @@ struct Song {
     album: Option<String>,
     /// Year the song was released
     year: Option<String>,
-    // /// Album cover of the song
-    // cover: Option<Vec<u8>>,
 }
 
 impl Song {
-    fn new(path: std::path::PathBuf) -> Self {
-        let file = File::open(&path).unwrap();
+    fn new(path: std::path::PathBuf) -> Result<Self, Box<dyn Error>> {
+        // the decoder probes the content, so this also rejects files with a misleading extension
+        let file = File::open(&path)?;
         let buf_reader = BufReader::new(file);
-        let source = Decoder::new(buf_reader).unwrap();
+        let source = Decoder::new(buf_reader)?;
         let sample_rate = source.sample_rate();
         let channels = source.channels();
         let mut song = Self {
             path,
             sample_rate,
             channels,
-            duration: Duration::from_secs(0),
+            duration: source.total_duration().unwrap_or(Duration::from_secs(0)),
             title: None,
             artist: None,
             album: None,
             year: None,
         };
-        song.parse_metadata().expect("Metadata parsing failed");
-        song
+        // a file without tags (e.g. wav) still plays, it just shows the file name
+        if song.parse_metadata().is_err() || song.title.is_none() {
+            let stem = song.path.file_stem().map(|stem| stem.to_string_lossy().to_string());
+            song.title = stem;
+        }
+        Ok(song)
     }
 
     fn parse_metadata(&mut self) -> Result<(), Box<dyn Error>> {
         let tagged_file = read_from_path(&self.path)?;
+        let properties = tagged_file.properties();
+        if !properties.duration().is_zero() {
+            self.duration = properties.duration();
+        }
+
         let tag = match tagged_file.primary_tag() {
             Some(primary_tag) => primary_tag,
-            None => tagged_file.first_tag().expect("ERROR: No tags found!"),
+            None => tagged_file.first_tag().ok_or("no tags found")?,
         };
-        let properties = tagged_file.properties();
-        self.duration = properties.duration();
-
         self.title = Some(tag.title().as_deref().unwrap_or("None").to_string());
         self.artist = Some(tag.artist().as_deref().unwrap_or("None").to_string());
         self.album = Some(tag.album().as_deref().unwrap_or("None").to_string());
@@ -90,10 +136,10 @@ impl Song {
 
         Ok(())
     }
-    fn create_source(&self) -> Decoder<BufReader<File>> {
-        let file = File::open(&self.path).unwrap();
+    fn create_source(&self) -> Result<Decoder<BufReader<File>>, Box<dyn Error>> {
+        let file = File::open(&self.path)?;
         let buf_reader = BufReader::new(file);
-        Decoder::new(buf_reader).unwrap()
+        Ok(Decoder::new(buf_reader)?)
     }
     fn get_filename(&self) -> &str {
         self.path.file_name().unwrap().to_str().unwrap()
@@ -119,103 +165,624 @@ struct Player {
     sink: Sink,
     /// Playback speed, 1.0 is normal speed
     playback_speed: f32,
+    /// Whether speed changes are time stretched instead of resampled, so the pitch stays the same
+    keep_pitch: bool,
+    /// Pitch shift, in semitones
+    pitch: f32,
+    /// Tempo of the time stretch every song is played through
+    tempo: Tempo,
     /// Current volume (0-1)
     volume: f32,
-    /// Current position in the song, in ms
+    /// Current position in the song, in seconds
     position: f64,
+    /// Seconds the seek keys move by
+    seek_step: f64,
     /// Whether the audio is playing or paused
     playing: bool,
     /// Loop type
     loop_type: LoopType,
     /// Whether the playlist is shuffled
     shuffle: bool,
+    /// Whether shuffling puts songs that usually get skipped later, and ones played through sooner
+    smart_shuffle: bool,
+    /// Songs in the order they were added, to undo shuffling
+    order: Vec<PathBuf>,
     /// Directory of the current playlist/folder
     folder_dir: String,
     /// Queue of songs to play
     queue: VecDeque<Song>,
     /// Current song being played
     current_song: Option<Song>,
+    /// Cuts the current song short, for crossfading into the next one
+    cut: CutHandle,
+    /// Loudness normalization of the current song, applied before the volume
+    gain: GainHandle,
+    /// Which ReplayGain songs are played at
+    gain_mode: GainMode,
+    /// ReplayGain tags and measured loudness of songs
+    loudness: Loudness,
+    /// Song already queued in the sink after the current one
+    preloaded: Option<Preloaded>,
+    /// Seconds the end of a song is faded into the next one over, 0 for plain gapless playback
+    crossfade: f64,
+    /// Previously played songs, most recent last
+    history: Vec<Song>,
+    /// Songs played since the playlist last started over, they come back when looping
+    played: Vec<Song>,
+    /// Files from the last folder load (or enqueue) that couldn't be decoded
+    errors: Vec<ScanError>,
+    /// Index of every song scanned so far
+    library: Library,
+    /// Every play so far, for the stats and smart shuffle
+    plays: PlayLog,
+    /// How much of the current song has been heard, it goes in the log when the song changes
+    listening: Option<Listening>,
+    /// Decoded samples of the playing song, shared with the visualizer
+    samples: Arc<Mutex<SampleBuffer>>,
+    /// Settings of the effects every song is played through
+    effects: Arc<EffectControls>,
+}
+
+/// The song queued in the sink after the current one, before the current one ends
+struct Preloaded {
+    song: Song,
+    cut: CutHandle,
+    gain: GainHandle,
+    /// Whether it's the current song again, when looping it
+    repeat: bool,
+    /// Whether taking it from the queue started the playlist over
+    refilled: bool,
 }
 
 impl Player {
     /// Checks if the current song has finished playing
     fn update_current_song(&mut self) {
-        // check if the sink is empty (song finished)
-        if self.sink.empty() {
-            match self.loop_type {
-                LoopType::None => {
-                    // if queue is empty, stop playing
-                    if self.queue.is_empty() {
-                        self.playing = false;
-                        self.sink.stop();
-                        self.current_song = None;
-                        return;
-                    } else {
-                        // load next song
-                        if let Some(next_song) = self.queue.pop_front() {
-                            self.current_song = Some(next_song);
-                            if let Some(ref song) = self.current_song {
-                                self.sink.append(song.create_source());
-                            }
-                            self.on_song_change();
-                        }
-                        self.on_song_change();
-                    }
-                }
-
-                LoopType::LoopOne => {
-                    // load the same song again
-                    if let Some(ref song) = self.current_song {
-                        self.sink.append(song.create_source());
-                    }
-                    self.on_song_change();
+        // the sink moved on to the preloaded song by itself
+        if self.preloaded.is_some() && self.sink.len() <= 1 {
+            self.finish_preloaded();
+            return;
+        }
+        // check if the sink is empty (song finished or skipped)
+        if !self.sink.empty() {
+            self.preload_if_ending();
+            return;
+        }
+        if self.current_song.is_none() && self.queue.is_empty() {
+            return;
+        }
+        match self.loop_type {
+            LoopType::LoopOne if self.current_song.is_some() => {
+                // load the same song again
+                if let Some(ref song) = self.current_song {
+                    self.cut = self.append(song, &self.gain);
                 }
-
-                LoopType::Loop => {
-                    // add current song to the end of the queue
-                    if let Some(ref song) = self.current_song {
-                        self.queue.push_back(song.clone());
-                    }
-                    // load next song
-                    if let Some(next_song) = self.queue.pop_front() {
-                        self.current_song = Some(next_song);
-                        if let Some(ref song) = self.current_song {
-                            self.sink.append(song.create_source());
-                        }
-                        self.on_song_change();
-                    }
+                self.on_song_change();
+            }
+            _ => {
+                self.advance();
+            }
+        }
+    }
+    /// Queues the next song in the sink shortly before the current one ends, so there's no gap between them
+    fn preload_if_ending(&mut self) {
+        let duration = self.get_duration();
+        let remaining = duration - self.position;
+        if self.preloaded.is_some() || duration <= 0.0 || remaining > PRELOAD_TIME + self.crossfade {
+            return;
+        }
+        let Some(current) = self.current_song.clone() else {
+            return;
+        };
+        let repeat = matches!(self.loop_type, LoopType::LoopOne);
+        let mut refilled = false;
+        let song = if repeat {
+            current.clone()
+        } else {
+            // counted as played now, so it comes back if this starts the playlist over
+            self.played.push(current.clone());
+            refilled = self.queue.is_empty();
+            match self.next_song() {
+                Some(song) => song,
+                None => {
+                    self.played.pop();
+                    return;
                 }
             }
+        };
 
-            // if queue is not empty, load next song
-
+        let gain = if repeat { self.gain.clone() } else { self.gain_for(&song) };
+        let fade = self.crossfade.min(duration / 2.0);
+        let cut_at = duration - fade;
+        let cut = if fade > 0.0 && cut_at > self.position + 0.5 {
+            self.append_crossfade(&current, &song, &gain, cut_at, fade)
+        } else {
+            None
+        };
+        let cut = cut.unwrap_or_else(|| self.append(&song, &gain));
+        self.preloaded = Some(Preloaded { song, cut, gain, repeat, refilled });
+    }
+    /// Queues the next song with the end of the current one fading into it, and ends the current one where the fade starts
+    fn append_crossfade(&self, current: &Song, next: &Song, gain: &GainHandle, cut_at: f64, fade: f64) -> Option<CutHandle> {
+        let cut_at = Duration::from_secs_f64(cut_at);
+        let mut tail = current.create_source().ok()?;
+        tail.try_seek(cut_at).ok()?;
+        // each side at its own song's gain
+        let tail = Gain::new(tail, self.gain.clone());
+        let source = Gain::new(next.create_source().ok()?, gain.clone());
+        let cut = self.append_source(Crossfade::new(tail, source, Duration::from_secs_f64(fade)));
+        self.cut.cut_at(cut_at);
+        Some(cut)
+    }
+    /// The current song ended and the sink is playing the preloaded one now
+    fn finish_preloaded(&mut self) {
+        let Some(preloaded) = self.preloaded.take() else {
+            return;
+        };
+        if let Some(song) = self.current_song.take() {
+            // it was counted as played when the next one was preloaded
+            if !preloaded.repeat {
+                self.push_history(song);
+            }
         }
+        self.current_song = Some(preloaded.song);
+        self.cut = preloaded.cut;
+        self.gain = preloaded.gain;
+        self.on_song_change();
     }
-    /// A callback that gets executed when the song changes.
-    fn on_song_change(&self) {
-        if let Some(song) = self.get_current_song() {
-            // do something? maybe?
+    /// Takes back the preloaded song, for when what plays next changes
+    fn cancel_preload(&mut self) {
+        let Some(preloaded) = self.preloaded.take() else {
+            return;
+        };
+        // it can't be taken out of the sink, but it can be cut to nothing
+        preloaded.cut.cut_at(Duration::ZERO);
+        self.cut.clear();
+        if preloaded.repeat {
+            return;
+        }
+        self.queue.push_front(preloaded.song);
+        let Some(current) = &self.current_song else {
+            return;
+        };
+        if preloaded.refilled {
+            // the current song went back into the queue with the rest of the playlist
+            if let Some(index) = self.queue.iter().rposition(|song| song.path == current.path) {
+                self.queue.remove(index);
+            }
+        } else {
+            self.played.pop();
         }
     }
-    /// Returns a list of songs in the current playlist/folder
-    fn get_songs_list(&self) -> Vec<Song> {
-        let mut songs = Vec::new();
-        for entry in fs::read_dir(&self.folder_dir).unwrap() {
-            let entry = entry.unwrap();
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("mp3") {
-                songs.push(Song::new(path));
+    fn push_history(&mut self, song: Song) {
+        self.history.push(song);
+        if self.history.len() > HISTORY_LENGTH {
+            self.history.remove(0);
+        }
+    }
+    /// Songs still to come with the preloaded one back in front, the queue as it is after cancel_preload
+    fn upcoming(&self) -> Vec<&Song> {
+        let mut songs: Vec<&Song> = self.queue.iter().collect();
+        let Some(preloaded) = self.preloaded.as_ref().filter(|preloaded| !preloaded.repeat) else {
+            return songs;
+        };
+        if let (true, Some(current)) = (preloaded.refilled, &self.current_song) {
+            if let Some(index) = songs.iter().rposition(|song| song.path == current.path) {
+                songs.remove(index);
             }
         }
+        songs.insert(0, &preloaded.song);
         songs
     }
-    fn skip(&mut self) {
+    /// Songs played so far, without the current one that preloading counts early
+    fn played_so_far(&self) -> &[Song] {
+        match &self.preloaded {
+            Some(preloaded) if !preloaded.repeat && !preloaded.refilled && self.current_song.is_some() => {
+                &self.played[..self.played.len().saturating_sub(1)]
+            }
+            _ => &self.played,
+        }
+    }
+    /// Moves the current song to the history and starts the next one
+    fn advance(&mut self) {
+        self.cancel_preload();
+        if let Some(song) = self.current_song.take() {
+            self.played.push(song.clone());
+            self.push_history(song);
+        }
+        match self.next_song() {
+            Some(song) => self.play_now(song),
+            None => {
+                // end of the queue, stop playing
+                self.playing = false;
+                self.sink.stop();
+                self.finish_listening();
+            }
+        }
+    }
+    /// Takes the next song from the queue, starting the playlist over when looping
+    fn next_song(&mut self) -> Option<Song> {
+        if self.queue.is_empty() && matches!(self.loop_type, LoopType::Loop) {
+            // everything played since the last time round comes back, whatever the loop type was then
+            self.queue.extend(self.played.drain(..));
+            if self.shuffle {
+                self.shuffle_queue();
+            } else {
+                self.unshuffle_queue();
+            }
+        }
+        self.queue.pop_front()
+    }
+    /// Replaces whatever is in the sink with a song
+    fn play_now(&mut self, song: Song) {
+        self.cancel_preload();
         self.sink.stop();
+        // so the position doesn't show the old song until the new one starts decoding
+        self.samples.lock().unwrap().reset(song.sample_rate, Duration::ZERO);
+        self.gain = self.gain_for(&song);
+        self.cut = self.append(&song, &self.gain);
+        self.current_song = Some(song);
+        self.on_song_change();
+    }
+    /// Goes back to the previous song, or to the start of the current one if it has been playing a while
+    fn previous(&mut self) {
+        self.cancel_preload();
+        if self.position > RESTART_THRESHOLD || self.history.is_empty() {
+            if let Some(song) = self.current_song.clone() {
+                self.play_now(song);
+            }
+            return;
+        }
+        let Some(song) = self.history.pop() else {
+            return;
+        };
+        // it's playing again, so it doesn't count as played this time round yet
+        if let Some(index) = self.played.iter().rposition(|played| played.path == song.path) {
+            self.played.remove(index);
+        }
+        if let Some(current) = self.current_song.take() {
+            self.queue.push_front(current);
+        }
+        self.play_now(song);
+    }
+    fn toggle_shuffle(&mut self) {
+        self.cancel_preload();
+        self.shuffle = !self.shuffle;
+        if self.shuffle {
+            self.shuffle_queue();
+        } else {
+            self.unshuffle_queue();
+        }
+    }
+    /// Randomises the upcoming songs, each one still plays once before the playlist repeats
+    fn shuffle_queue(&mut self) {
+        if !self.smart_shuffle {
+            self.queue.make_contiguous().shuffle(&mut rand::rng());
+            return;
+        }
+        // weighted random order (Efraimidis-Spirakis), so songs that usually get skipped tend to come up later
+        let weights = self.plays.weights();
+        let mut keyed: Vec<(f64, Song)> = self
+            .queue
+            .drain(..)
+            .map(|song| {
+                let weight = weights.get(song.path.as_path()).copied().unwrap_or(0.5);
+                (rand::random::<f64>().powf(1.0 / weight), song)
+            })
+            .collect();
+        keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        self.queue.extend(keyed.into_iter().map(|(_, song)| song));
+    }
+    fn toggle_smart_shuffle(&mut self) {
+        self.cancel_preload();
+        self.smart_shuffle = !self.smart_shuffle;
+        if self.shuffle {
+            self.shuffle_queue();
+        }
+    }
+    /// Puts the upcoming songs back in the order they were added
+    fn unshuffle_queue(&mut self) {
+        let order: HashMap<&PathBuf, usize> = self.order.iter().enumerate().map(|(i, path)| (path, i)).collect();
+        self.queue.make_contiguous().sort_by_key(|song| order.get(&song.path).copied().unwrap_or(usize::MAX));
+    }
+    fn cycle_loop_type(&mut self) {
+        self.cancel_preload();
+        self.loop_type = match self.loop_type {
+            LoopType::None => LoopType::Loop,
+            LoopType::Loop => LoopType::LoopOne,
+            LoopType::LoopOne => LoopType::None,
+        };
+    }
+    /// Moves the crossfade to the next length, back to off after the longest
+    fn cycle_crossfade(&mut self) {
+        self.cancel_preload();
+        let index = CROSSFADE_STEPS.iter().position(|&step| step == self.crossfade).map_or(0, |index| index + 1);
+        self.crossfade = CROSSFADE_STEPS[index % CROSSFADE_STEPS.len()];
+    }
+    /// Appends a song to the sink at a gain, returns the handle to cut it short
+    fn append(&self, song: &Song, gain: &GainHandle) -> CutHandle {
+        match song.create_source() {
+            Ok(source) => self.append_source(Gain::new(source, gain.clone())),
+            // if the file went away since it was scanned the sink stays empty and the next update moves on
+            Err(_) => CutHandle::new(),
+        }
+    }
+    /// Appends a source to the sink through the effects, tapping its samples for the visualizer
+    fn append_source<S>(&self, source: S) -> CutHandle
+    where
+        S: Source + Send + 'static,
+        S::Item: rodio::Sample + Send,
+    {
+        let cut = CutHandle::new();
+        let source = Effects::new(source, self.effects.clone());
+        let source = Tap::new(Cut::new(source, cut.clone()), self.samples.clone());
+        // outside the tap, so the position stays in song time whatever the tempo
+        self.sink.append(Stretch::new(source, self.tempo.clone()));
+        cut
+    }
+    /// A callback that gets executed when the song changes.
+    fn on_song_change(&mut self) {
+        self.finish_listening();
+        self.listening = self.current_song.as_ref().map(Listening::new);
+        // so the next song is measured by the time it starts
+        if self.gain_mode != GainMode::Off {
+            if let Some(next) = self.queue.front() {
+                self.loudness.request(next);
+            }
+        }
+    }
+    /// Linear gain to play a song at for the gain mode
+    fn song_gain(&mut self, song: &Song) -> f32 {
+        let album: Vec<&Song> = match (self.gain_mode, song.get_album()) {
+            (GainMode::Album, Some(album)) => self
+                .library
+                .songs()
+                .into_iter()
+                .filter(|other| {
+                    other.path != song.path && other.path.parent() == song.path.parent() && other.get_album() == Some(album)
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        self.loudness.gain(song, &album, self.gain_mode)
+    }
+    fn gain_for(&mut self, song: &Song) -> GainHandle {
+        GainHandle::new(self.song_gain(song))
+    }
+    /// Sets the gains of the songs in the sink again, after a measurement or a change of gain mode
+    fn update_gains(&mut self) {
+        if let Some(song) = self.current_song.clone() {
+            let gain = self.song_gain(&song);
+            self.gain.set(gain);
+        }
+        if let Some(song) = self.preloaded.as_ref().map(|preloaded| preloaded.song.clone()) {
+            let gain = self.song_gain(&song);
+            if let Some(preloaded) = &self.preloaded {
+                preloaded.gain.set(gain);
+            }
+        }
+    }
+    /// Picks up loudness measured in the background
+    fn update_loudness(&mut self) {
+        if self.loudness.poll() {
+            self.update_gains();
+        }
+    }
+    fn cycle_gain_mode(&mut self) {
+        self.gain_mode = self.gain_mode.next();
+        self.update_gains();
+    }
+    /// Logs the play of the song that was listened to last
+    fn finish_listening(&mut self) {
+        let Some(listening) = self.listening.take() else {
+            return;
+        };
+        if let Some(play) = listening.finish(self.crossfade) {
+            // a play missing from the stats isn't worth interrupting anything over
+            let _ = self.plays.record(play);
+        }
+    }
+    /// Returns a list of songs in the current playlist/folder and its subfolders
+    fn get_songs_list(&mut self) -> (Vec<Song>, Vec<ScanError>) {
+        self.library.scan(Path::new(&self.folder_dir), true)
+    }
+    /// Replaces the queue with the songs in a folder, or appends them to it
+    fn load_folder(&mut self, folder: &Path, replace: bool) {
+        self.folder_dir = folder.to_string_lossy().to_string();
+        let (songs, errors) = self.get_songs_list();
+        self.add_songs(songs, errors, replace);
+    }
+    /// Replaces the queue with the tracks of an m3u playlist, or appends them to it
+    fn load_playlist(&mut self, path: &Path, replace: bool) {
+        match playlist::read_m3u(path) {
+            Ok(tracks) => {
+                let (songs, errors) = self.library.load(tracks);
+                self.add_songs(songs, errors, replace);
+            }
+            Err(error) => {
+                self.errors = vec![ScanError { path: path.to_path_buf(), error: error.to_string() }];
+            }
+        }
+    }
+    /// Saves the current song and the queue as an m3u8 playlist
+    fn save_playlist(&self, path: &Path) -> io::Result<()> {
+        let songs: Vec<&Song> = self.current_song.iter().chain(self.upcoming()).collect();
+        playlist::write_m3u(path, &songs)
+    }
+    fn add_songs(&mut self, songs: Vec<Song>, errors: Vec<ScanError>, replace: bool) {
+        self.cancel_preload();
+        self.errors = errors;
+        if replace {
+            self.queue.clear();
+            self.order.clear();
+            self.played.clear();
+            self.current_song = None;
+            self.sink.stop();
+            self.finish_listening();
+        }
+        self.order.extend(songs.iter().map(|song| song.path.clone()));
+        self.queue.extend(songs);
+        if self.shuffle {
+            self.shuffle_queue();
+        }
+        self.start_if_idle();
+        // remembered right away too, so a crash doesn't lose the folder that was picked
+        let _ = self.session().save();
+    }
+    /// Queue and settings to pick up from on the next start
+    fn session(&self) -> Session {
+        Session {
+            folder: self.folder_dir.clone(),
+            current: self.current_song.as_ref().map(|song| song.path.clone()),
+            position: self.position,
+            queue: self.upcoming().iter().map(|song| song.path.clone()).collect(),
+            played: self.played_so_far().iter().map(|song| song.path.clone()).collect(),
+            order: self.order.clone(),
+            shuffle: self.shuffle,
+            smart_shuffle: self.smart_shuffle,
+            loop_type: self.loop_type,
+            volume: self.volume,
+            playback_speed: self.playback_speed,
+            crossfade: self.crossfade,
+            gain_mode: self.gain_mode,
+            keep_pitch: self.keep_pitch,
+            pitch: self.pitch,
+        }
+    }
+    /// Restores the queue, current song and settings of a previous session
+    fn restore_session(&mut self, session: Session) {
+        self.folder_dir = session.folder;
+        self.shuffle = session.shuffle;
+        self.smart_shuffle = session.smart_shuffle;
+        self.loop_type = session.loop_type;
+        self.set_volume(session.volume);
+        self.keep_pitch = session.keep_pitch;
+        self.pitch = session.pitch;
+        self.set_playback_speed(session.playback_speed);
+        self.crossfade = session.crossfade;
+        self.gain_mode = session.gain_mode;
+        let (songs, mut errors) = self.library.load(session.queue);
+        self.queue.extend(songs);
+        let (played, played_errors) = self.library.load(session.played);
+        self.played = played;
+        errors.extend(played_errors);
+        self.order = session.order;
+        if let Some(path) = session.current {
+            match self.library.song(&path) {
+                Ok(song) => {
+                    self.play_now(song);
+                    self.play();
+                    self.set_position(session.position);
+                }
+                Err(error) => {
+                    errors.push(ScanError { path, error });
+                }
+            }
+        }
+        self.errors = errors;
+        self.start_if_idle();
+    }
+    /// Adds a single file to the end of the queue
+    fn enqueue(&mut self, path: PathBuf) {
+        match self.library.song(&path) {
+            Ok(song) => {
+                self.enqueue_songs(vec![song]);
+            }
+            Err(error) => {
+                self.errors = vec![ScanError { path, error }];
+            }
+        }
+    }
+    /// Adds songs to the end of the queue
+    fn enqueue_songs(&mut self, songs: Vec<Song>) {
+        self.cancel_preload();
+        self.order.extend(songs.iter().map(|song| song.path.clone()));
+        self.queue.extend(songs);
+        self.start_if_idle();
+    }
+    /// Starts the next song in the queue if nothing is playing
+    fn start_if_idle(&mut self) {
+        if self.current_song.is_some() {
+            return;
+        }
+        if let Some(song) = self.next_song() {
+            self.play_now(song);
+            self.play();
+        }
+    }
+    /// Skips to the next song, even when looping the current one
+    fn skip(&mut self) {
+        self.advance();
+    }
+    /// Moves a song in the queue up (-1) or down (1), returns where it ended up
+    fn move_in_queue(&mut self, index: usize, direction: i32) -> usize {
+        self.cancel_preload();
+        let target = index as i64 + direction as i64;
+        if index >= self.queue.len() || target < 0 || target as usize >= self.queue.len() {
+            return index;
+        }
+        self.queue.swap(index, target as usize);
+        target as usize
+    }
+    fn remove_from_queue(&mut self, index: usize) {
+        self.cancel_preload();
+        self.queue.remove(index);
+    }
+    /// Plays a song from the queue now, the ones before it count as played
+    fn jump_in_queue(&mut self, index: usize) {
+        self.cancel_preload();
+        if index >= self.queue.len() {
+            return;
+        }
+        if let Some(song) = self.current_song.take() {
+            self.played.push(song.clone());
+            self.push_history(song);
+        }
+        let skipped: Vec<Song> = self.queue.drain(..index).collect();
+        self.played.extend(skipped);
+        if let Some(song) = self.queue.pop_front() {
+            self.play_now(song);
+            self.play();
+        }
+    }
+    /// Moves a song to the front of the queue
+    fn play_next(&mut self, index: usize) {
+        self.cancel_preload();
+        if let Some(song) = self.queue.remove(index) {
+            self.queue.push_front(song);
+        }
+    }
+    /// Empties the queue, the playlist is only the current song after this
+    fn clear_queue(&mut self) {
+        self.cancel_preload();
+        self.queue.clear();
+        self.played.clear();
+        self.order = self.current_song.iter().map(|song| song.path.clone()).collect();
     }
     fn set_playback_speed(&mut self, speed: f32) {
         let speed = speed.max(0.5).min(2.0);
         self.playback_speed = speed;
-        self.sink.set_speed(speed);
+        self.apply_speed();
+    }
+    fn set_pitch(&mut self, pitch: f32) {
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        self.apply_speed();
+    }
+    fn toggle_keep_pitch(&mut self) {
+        self.keep_pitch = !self.keep_pitch;
+        self.apply_speed();
+    }
+    /// Splits the speed and pitch between the sink, which resamples, and the time stretch
+    fn apply_speed(&mut self) {
+        let pitch = 2f32.powf(self.pitch / 12.0);
+        if self.keep_pitch {
+            // resampled to the new pitch, stretched to make up the tempo
+            self.sink.set_speed(pitch);
+            self.tempo.set(self.playback_speed / pitch);
+        } else {
+            // resampled for both, stretched back to undo the tempo change of the pitch shift
+            self.sink.set_speed(self.playback_speed * pitch);
+            self.tempo.set(1.0 / pitch);
+        }
     }
     fn get_playback_speed(&self) -> f32 {
         self.playback_speed
@@ -228,8 +795,42 @@ impl Player {
     fn get_volume(&self) -> f32 {
         self.volume
     }
+    /// Seeks to a position in the current song, in seconds
     fn set_position(&mut self, position: f64) {
-        self.position = position;
+        if self.current_song.is_none() {
+            return;
+        }
+        // the crossfade would start from where the song was cut before the seek
+        self.cancel_preload();
+        let duration = self.get_duration();
+        let mut position = position.max(0.0);
+        if duration > 0.0 {
+            position = position.min(duration);
+        }
+        // the sink's speed (playback speed and pitch) scales seek targets back up, so it's divided out first
+        let target = position / self.sink.speed() as f64;
+        if self.sink.try_seek(Duration::from_secs_f64(target)).is_ok() {
+            self.position = position;
+        }
+    }
+    fn seek_to_fraction(&mut self, fraction: f64) {
+        self.set_position(self.get_duration() * fraction.clamp(0.0, 1.0));
+    }
+    /// Moves the seek step to the next shorter (-1) or longer (1) length
+    fn change_seek_step(&mut self, direction: i32) {
+        let index = SEEK_STEPS.iter().position(|&step| step == self.seek_step).unwrap_or(1) as i32;
+        let index = (index + direction).clamp(0, SEEK_STEPS.len() as i32 - 1);
+        self.seek_step = SEEK_STEPS[index as usize];
+    }
+    /// Reads the position from the decoded samples, sink.get_pos() drifts once the speed changes
+    fn update_position(&mut self) {
+        self.position = match self.current_song {
+            Some(_) => self.samples.lock().unwrap().position().as_secs_f64(),
+            None => 0.0,
+        };
+        if let Some(listening) = &mut self.listening {
+            listening.update(self.position, self.playing);
+        }
     }
     fn get_position(&self) -> f64 {
         self.position
@@ -254,6 +855,8 @@ impl Player {
     fn play(&mut self) {
         self.playing = true;
         self.sink.play();
+        // after the end of the queue, start over (or from the playlist again when looping)
+        self.start_if_idle();
     }
     fn set_playing(&mut self, playing: bool) {
         self.playing = playing;
@@ -266,19 +869,95 @@ impl Player {
     }
 }
 
+/// Lengths the seek step can be set to, in seconds
+const SEEK_STEPS: [f64; 5] = [1.0, 5.0, 10.0, 30.0, 60.0];
+/// Songs kept for going back with previous
+const HISTORY_LENGTH: usize = 200;
+/// Seconds into a song after which previous restarts it instead
+const RESTART_THRESHOLD: f64 = 3.0;
+/// Seconds before the end of a song the next one is queued in the sink, on top of the crossfade
+const PRELOAD_TIME: f64 = 3.0;
+/// Lengths the crossfade can be set to, in seconds
+const CROSSFADE_STEPS: [f64; 5] = [0.0, 2.0, 4.0, 8.0, 12.0];
+/// Semitones the pitch can be shifted by, either way
+const MAX_PITCH: f32 = 12.0;
+/// How long status messages stay up
+const MESSAGE_DURATION: Duration = Duration::from_secs(4);
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 enum LoopType {
     /// No loop
     None,
     /// Loop the playlist
+    #[default]
     Loop,
     /// Loop the current song
     LoopOne,
 }
 
+/// Pane that receives the navigation keys
+#[derive(PartialEq)]
+enum Focus {
+    Player,
+    FilePicker,
+    /// Library search, shown over the other panes
+    Library,
+    /// Effects, shown in place of the player pane
+    Effects,
+    /// Play stats, shown over the other panes
+    Stats,
+    /// Queue editing, in the queue pane
+    Queue,
+}
+
+/// What a text prompt is asking for
+enum Prompt {
+    /// Name or path to save the queue to as a playlist
+    SavePlaylist,
+}
+
+impl Prompt {
+    fn title(&self) -> &'static str {
+        match self {
+            Prompt::SavePlaylist => " Save queue as playlist (name, or path to an .m3u) ",
+        }
+    }
+}
+
+/// Line of text being typed in, it takes every key until it's submitted or cancelled
+struct Input {
+    prompt: Prompt,
+    text: String,
+}
+
 struct App {
     _stream: OutputStream,
     player: Player,
-    start_time: Instant,
+    visualizer: Visualizer,
+    cover: Cover,
+    lyrics: Lyrics,
+    /// Whether the lyrics are shown in place of the queue
+    show_lyrics: bool,
+    file_picker: FilePicker,
+    library_view: LibraryView,
+    stats_view: StatsView,
+    effects_pane: EffectsPane,
+    /// Selected entry of the queue, while it's focused
+    queue_selected: usize,
+    focus: Focus,
+    /// Keys bound to each action, from the config file
+    keys: Keymap,
+    /// Scroll offset of the key binding help, shown over everything while it's Some
+    help: Option<usize>,
+    /// Text prompt shown over everything else
+    input: Option<Input>,
+    /// Status message and when it was shown, it goes away after MESSAGE_DURATION
+    message: Option<(String, Instant)>,
+    /// Socket other programs control the player through, None if it couldn't be opened
+    control: Option<Server>,
+    /// Terminal area of the last frame, to find what the mouse is over
+    area: Rect,
     last_frame_time: Instant,
     fps: f64,
     frame_times: VecDeque<f64>,
@@ -290,6 +969,9 @@ impl App {
         // _stream must live as long as the sink
         let (_stream, stream_handle) = OutputStream::try_default().unwrap();
         let sink = Sink::try_new(&stream_handle).unwrap();
+        let samples = SampleBuffer::shared();
+        // before anything is drawn, it sets the palette
+        let (keys, problems) = config::load();
 
         let mut app = Self {
             _stream,
@@ -297,48 +979,105 @@ impl App {
             player: Player {
                 sink,
                 playback_speed: 1.0,
+                keep_pitch: false,
+                pitch: 0.0,
+                tempo: Tempo::new(),
                 volume: 0.05,
                 position: 0.0,
+                seek_step: SEEK_STEPS[1],
                 playing: false,
                 loop_type: LoopType::Loop,
                 shuffle: false,
+                smart_shuffle: false,
+                order: Vec::new(),
                 folder_dir: String::new(),
                 queue: VecDeque::new(),
                 current_song: None,
+                cut: CutHandle::new(),
+                gain: GainHandle::new(1.0),
+                gain_mode: GainMode::default(),
+                loudness: Loudness::open(),
+                preloaded: None,
+                crossfade: 0.0,
+                history: Vec::new(),
+                played: Vec::new(),
+                errors: Vec::new(),
+                library: Library::open(),
+                plays: PlayLog::open(),
+                listening: None,
+                samples: samples.clone(),
+                effects: EffectControls::shared(EffectSettings::load()),
             },
-            start_time: Instant::now(),
+            visualizer: Visualizer::new(samples),
+            cover: Cover::new(),
+            lyrics: Lyrics::new(),
+            show_lyrics: false,
+            file_picker: FilePicker::new(PathBuf::from(".")),
+            library_view: LibraryView::new(),
+            stats_view: StatsView::new(),
+            effects_pane: EffectsPane::new(),
+            queue_selected: 0,
+            focus: Focus::Player,
+            keys,
+            help: None,
+            input: None,
+            message: None,
+            control: None,
+            area: Rect::default(),
             last_frame_time: Instant::now(),
             fps: 0.0,
             frame_times: VecDeque::new(),
             exit: false,
         };
         app.initialize();
+        if let Some(problem) = problems.first() {
+            let more = match problems.len() {
+                1 => String::new(),
+                n => format!(" (and {} more)", n - 1),
+            };
+            app.notify(format!("{problem}{more}"));
+        }
         app
     }
 
     fn initialize(&mut self) {
-        // init - subject to change
-        let folder = fs::canonicalize("songs").unwrap();
-        self.player.folder_dir = folder.clone().to_str().unwrap().to_string();
-        let songs = self.player.get_songs_list();
+        self.player.apply_speed();
+        self.player.sink.set_volume(self.player.get_volume());
 
-        // add songs to queue
-        for song in songs {
-            self.player.queue.push_back(song);
+        if let Some(session) = Session::load() {
+            self.player.restore_session(session);
         }
-        // play first song
-        if let Some(song) = self.player.queue.pop_front() {
-            self.player.current_song = Some(song);
-            if let Some(ref song) = self.player.current_song {
-                self.player.sink.append(song.create_source());
+        // the last session's queue, otherwise its folder, then ./songs, otherwise start with an empty queue in the current dir
+        let folder = Some(PathBuf::from(&self.player.folder_dir))
+            .filter(|folder| folder.is_dir())
+            .or_else(|| fs::canonicalize("songs").ok());
+        if self.player.current_song.is_none() {
+            if let Some(folder) = &folder {
+                self.player.load_folder(folder, true);
             }
-            self.player.on_song_change();
         }
+        let dir = folder.unwrap_or_else(|| fs::canonicalize(".").unwrap_or_else(|_| PathBuf::from(".")));
+        self.file_picker = FilePicker::new(dir);
 
-        self.player.sink.set_speed(self.player.get_playback_speed());
-        self.player.sink.set_volume(self.player.get_volume());
-        self.player.sink.play();
-        self.player.playing = true;
+        match Server::start() {
+            Ok(server) => {
+                self.control = Some(server);
+            }
+            Err(error) => {
+                self.notify(format!("control socket unavailable: {error}"));
+            }
+        }
+    }
+
+    fn save_session(&self) {
+        // not being able to remember the session isn't worth failing the exit over
+        let _ = self.player.session().save();
+        let _ = self.player.effects.settings().save();
+    }
+
+    /// Shows a status message at the bottom of the screen for a few seconds
+    fn notify(&mut self, message: String) {
+        self.message = Some((message, Instant::now()));
     }
 
     pub fn run(mut self, mut terminal: DefaultTerminal) -> io::Result<()> {
@@ -359,13 +1098,29 @@ impl App {
             self.fps = self.frame_times.len() as f64 / total_time;
 
             self.player.update_current_song();
+            self.player.update_loudness();
+            // the pitch the samples are heard at, which the time stretch leaves alone
+            self.visualizer.update(self.player.sink.speed());
 
             // song position
-            self.player.position = self.player.sink.get_pos().as_secs_f64();
+            self.player.update_position();
+
+            self.lyrics.update(self.player.get_current_song());
+            if !too_small(self.area) {
+                let cover = panes(self.area).cover;
+                self.cover.update(
+                    self.player.get_current_song().map(|song| song.path.as_path()),
+                    cover.width.saturating_sub(2) as usize,
+                    cover.height.saturating_sub(2) as usize,
+                );
+            }
 
-            terminal.draw(|frame| self.draw(frame))?;
+            self.area = terminal.draw(|frame| self.draw(frame))?.area;
             self.handle_events()?;
+            self.handle_control();
         }
+        self.player.finish_listening();
+        self.save_session();
         Ok(())
     }
 
@@ -379,58 +1134,560 @@ impl App {
                 Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
                     self.handle_key_event(key_event)
                 }
+                Event::Mouse(mouse_event) => {
+                    self.handle_mouse_event(mouse_event)
+                }
                 _ => {}
             };
         }
         Ok(())
     }
 
+    /// Runs the commands that came in on the control socket, every one is answered with a JSON line
+    fn handle_control(&mut self) {
+        let Some(control) = &self.control else {
+            return;
+        };
+        let requests: Vec<Request> = control.requests.try_iter().collect();
+        for request in requests {
+            let reply = match request.command.and_then(|command| self.run_command(command)) {
+                Ok(()) => self.now_playing(),
+                Err(error) => serde_json::json!({ "ok": false, "error": error }),
+            };
+            // the client may have hung up already
+            let _ = request.reply.send(reply.to_string());
+        }
+    }
+
+    fn run_command(&mut self, command: Command) -> Result<(), String> {
+        match command {
+            Command::Play => {
+                self.player.play();
+            }
+            Command::Pause => {
+                self.player.pause();
+            }
+            Command::Toggle => {
+                self.player.toggle_playing();
+            }
+            Command::Next => {
+                self.player.skip();
+            }
+            Command::Previous => {
+                self.player.previous();
+            }
+            Command::Seek(amount) => {
+                if self.player.get_current_song().is_none() {
+                    return Err("nothing is playing".to_string());
+                }
+                self.player.set_position(amount.apply(self.player.get_position()));
+            }
+            Command::Volume(amount) => {
+                let volume = amount.apply(self.player.get_volume() as f64 * 100.0) / 100.0;
+                self.player.set_volume(volume as f32);
+            }
+            Command::Enqueue(path) => {
+                if !path.exists() {
+                    return Err(format!("{} doesn't exist", path.display()));
+                }
+                if playlist::is_playlist_file(&path) {
+                    self.player.load_playlist(&path, false);
+                } else if path.is_dir() {
+                    self.player.load_folder(&path, false);
+                } else {
+                    self.player.enqueue(path.clone());
+                }
+                // a folder is still added when some of its files can't be read
+                if let Some(error) = self.player.errors.iter().find(|error| error.path == path) {
+                    return Err(error.error.clone());
+                }
+            }
+            Command::NowPlaying => {}
+        }
+        Ok(())
+    }
+
+    /// State of the player, as sent back to control socket clients
+    fn now_playing(&self) -> serde_json::Value {
+        let song = self.player.get_current_song();
+        serde_json::json!({
+            "ok": true,
+            "playing": self.player.is_playing() && song.is_some(),
+            "path": song.map(|song| song.path.to_string_lossy()),
+            "title": song.and_then(Song::get_title),
+            "artist": song.and_then(Song::get_artist),
+            "album": song.and_then(Song::get_album),
+            "position": self.player.get_position(),
+            "duration": self.player.get_duration(),
+            "volume": (self.player.get_volume() * 100.0).round(),
+            "queue": self.player.upcoming().len(),
+        })
+    }
+
     fn handle_key_event(&mut self, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char('q') | KeyCode::Backspace | KeyCode::Esc => {
+        if self.input.is_some() {
+            self.handle_input_key(key_event);
+            return;
+        }
+        if self.help.is_some() {
+            self.handle_help_key(key_event);
+            return;
+        }
+        if self.focus == Focus::FilePicker && self.handle_file_picker_key(key_event) {
+            return;
+        }
+        if self.focus == Focus::Library {
+            self.handle_library_key(key_event);
+            return;
+        }
+        if self.focus == Focus::Stats {
+            self.handle_stats_key(key_event);
+            return;
+        }
+        if self.focus == Focus::Effects && self.handle_effects_key(key_event) {
+            return;
+        }
+        if self.focus == Focus::Queue && self.handle_queue_key(key_event) {
+            return;
+        }
+        let Some(action) = self.keys.action(Context::Player, key_event) else {
+            return;
+        };
+        match action {
+            Action::FilePicker => {
+                self.focus = Focus::FilePicker;
+            }
+            Action::Quit => {
                 self.exit();
             }
-            KeyCode::Char(' ') => {
+            Action::Help => {
+                self.help = Some(0);
+            }
+            Action::TogglePlaying => {
                 self.player.toggle_playing();
             }
-            KeyCode::Up => {
+            Action::VolumeUp => {
                 let volume = self.player.get_volume() + 0.01;
                 self.player.set_volume(volume);
             }
-            KeyCode::Down => {
+            Action::VolumeDown => {
                 let volume = self.player.get_volume() - 0.01;
                 self.player.set_volume(volume);
             }
-            KeyCode::Char('o') => {
+            Action::SpeedDown => {
                 let speed = self.player.get_playback_speed() - 0.05;
                 self.player.set_playback_speed(speed);
             }
-            KeyCode::Char('p') => {
+            Action::SpeedUp => {
                 let speed = self.player.get_playback_speed() + 0.05;
                 self.player.set_playback_speed(speed);
             }
-            KeyCode::Enter => {
+            Action::KeepPitch => {
+                self.player.toggle_keep_pitch();
+            }
+            Action::PitchDown => {
+                let pitch = self.player.pitch - 1.0;
+                self.player.set_pitch(pitch);
+            }
+            Action::PitchUp => {
+                let pitch = self.player.pitch + 1.0;
+                self.player.set_pitch(pitch);
+            }
+            Action::Next => {
                 // skip
                 self.player.skip();
             }
-            KeyCode::Char('l') => {
-                let loop_type = match self.player.loop_type {
-                    LoopType::None => LoopType::Loop,
-                    LoopType::Loop => LoopType::LoopOne,
-                    LoopType::LoopOne => LoopType::None,
-                };
-                self.player.loop_type = loop_type;
+            Action::Previous => {
+                self.player.previous();
+            }
+            Action::Shuffle => {
+                self.player.toggle_shuffle();
+            }
+            Action::SmartShuffle => {
+                self.player.toggle_smart_shuffle();
+            }
+            Action::Stats => {
+                self.focus = Focus::Stats;
+                self.stats_view.refresh(&self.player.plays);
+            }
+            Action::Effects => {
+                self.focus = Focus::Effects;
+            }
+            Action::Lyrics => {
+                self.show_lyrics = !self.show_lyrics;
+                if self.show_lyrics && self.focus == Focus::Queue {
+                    self.focus = Focus::Player;
+                }
+            }
+            Action::Queue => {
+                // the queue is where the lyrics would be
+                self.focus = Focus::Queue;
+                self.show_lyrics = false;
+            }
+            Action::Library => {
+                self.focus = Focus::Library;
+                self.library_view.refresh(&self.player.library);
+            }
+            Action::SavePlaylist => {
+                self.input = Some(Input { prompt: Prompt::SavePlaylist, text: String::new() });
+            }
+            Action::SeekBackward => {
+                let position = self.player.get_position() - self.player.seek_step;
+                self.player.set_position(position);
+            }
+            Action::SeekForward => {
+                let position = self.player.get_position() + self.player.seek_step;
+                self.player.set_position(position);
+            }
+            Action::SeekStepDown => {
+                self.player.change_seek_step(-1);
+            }
+            Action::SeekStepUp => {
+                self.player.change_seek_step(1);
+            }
+            Action::Jump(tenth) => {
+                // jump to 0% - 90%
+                self.player.seek_to_fraction(tenth as f64 / 10.0);
+            }
+            Action::Loop => {
+                self.player.cycle_loop_type();
+            }
+            Action::Crossfade => {
+                self.player.cycle_crossfade();
+            }
+            Action::GainMode => {
+                self.player.cycle_gain_mode();
+            }
+            _ => {}
+        }
+    }
+
+    /// Keys while the help is open, scrolling it or closing it
+    fn handle_help_key(&mut self, key_event: KeyEvent) {
+        let Some(scroll) = self.help else {
+            return;
+        };
+        self.help = match key_event.code {
+            KeyCode::Up | KeyCode::Char('k') => Some(scroll.saturating_sub(1)),
+            KeyCode::Down | KeyCode::Char('j') => Some(scroll + 1),
+            KeyCode::PageUp => Some(scroll.saturating_sub(10)),
+            KeyCode::PageDown => Some(scroll + 10),
+            _ => None,
+        };
+    }
+
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent) {
+        if too_small(self.area) {
+            return;
+        }
+        // overlays, prompts and the effects pane cover the progress bar or the queue
+        let covered =
+            matches!(self.focus, Focus::Library | Focus::Stats | Focus::Effects) || self.input.is_some() || self.help.is_some();
+        if covered {
+            return;
+        }
+        let Panes { progress, queue, .. } = panes(self.area);
+        let over_queue = !self.show_lyrics && queue.contains(Position::new(mouse_event.column, mouse_event.row));
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left) => {
+                // click or drag on the progress bar to seek
+                if mouse_event.row == progress.y {
+                    if let Some(fraction) = self.progress_fraction(progress, mouse_event.column) {
+                        self.player.seek_to_fraction(fraction);
+                    }
+                }
+                // click on a queue entry to select it, rows start under the border, loop line and gap
+                if over_queue && matches!(mouse_event.kind, MouseEventKind::Down(_)) {
+                    let (start, rows) = self.queue_rows(queue.height as usize);
+                    let row = (mouse_event.row as usize).checked_sub(queue.y as usize + 3);
+                    if let Some(row) = row.filter(|&row| row < rows && start + row < self.player.upcoming().len()) {
+                        self.focus = Focus::Queue;
+                        self.queue_selected = start + row;
+                    }
+                }
+            }
+            MouseEventKind::ScrollUp if over_queue => {
+                self.focus = Focus::Queue;
+                self.queue_selected = self.selected_in_queue().unwrap_or(0).saturating_sub(1);
+            }
+            MouseEventKind::ScrollDown if over_queue => {
+                self.focus = Focus::Queue;
+                self.queue_selected = self.selected_in_queue().map_or(0, |selected| selected + 1);
+            }
+            _ => {}
+        }
+    }
+
+    /// Keys for the file picker while it has focus, returns whether the key was used
+    fn handle_file_picker_key(&mut self, key_event: KeyEvent) -> bool {
+        match self.keys.action(Context::FilePicker, key_event) {
+            Some(Action::Back) => {
+                self.focus = Focus::Player;
+            }
+            Some(Action::Up) => {
+                self.file_picker.select_previous();
+            }
+            Some(Action::Down) => {
+                self.file_picker.select_next();
+            }
+            Some(Action::Open) => {
+                // enter a directory, play a playlist, or enqueue a single file
+                if let Some(path) = self.file_picker.enter() {
+                    if playlist::is_playlist_file(&path) {
+                        self.player.load_playlist(&path, true);
+                    } else {
+                        self.player.enqueue(path);
+                    }
+                }
+            }
+            Some(Action::Leave) => {
+                self.file_picker.leave();
+            }
+            Some(Action::Replace) => {
+                self.load_selected(true);
+            }
+            Some(Action::Add) => {
+                self.load_selected(false);
+            }
+            Some(Action::Playlists) => {
+                // saved playlists
+                let dir = playlist::playlists_dir();
+                let _ = fs::create_dir_all(&dir);
+                self.file_picker.open(dir);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Replaces the queue with the selected folder or playlist, or appends it
+    fn load_selected(&mut self, replace: bool) {
+        match self.file_picker.selected_target() {
+            (path, EntryKind::Playlist) => {
+                self.player.load_playlist(&path, replace);
+            }
+            (folder, _) => {
+                self.player.load_folder(&folder, replace);
+            }
+        }
+    }
+
+    /// Keys for the effects pane while it has focus, returns whether the key was used
+    fn handle_effects_key(&mut self, key_event: KeyEvent) -> bool {
+        match self.keys.action(Context::Effects, key_event) {
+            Some(Action::Back) => {
+                self.focus = Focus::Player;
+            }
+            Some(Action::Up) => {
+                self.effects_pane.select_previous();
+            }
+            Some(Action::Down) => {
+                self.effects_pane.select_next();
+            }
+            Some(Action::Decrease) => {
+                self.player.effects.update(|settings| self.effects_pane.adjust(settings, -1));
+            }
+            Some(Action::Increase) => {
+                self.player.effects.update(|settings| self.effects_pane.adjust(settings, 1));
+            }
+            Some(Action::Reset) => {
+                self.player.effects.update(|settings| self.effects_pane.reset(settings));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Keys for the library search, typing goes to the query unless the key is bound
+    fn handle_library_key(&mut self, key_event: KeyEvent) {
+        let library = &self.player.library;
+        match self.keys.action(Context::Library, key_event) {
+            Some(Action::Back) => {
+                if !self.library_view.close_group(library) {
+                    self.focus = Focus::Player;
+                }
+            }
+            Some(Action::NextView) => {
+                self.library_view.cycle_view(1, library);
+            }
+            Some(Action::PreviousView) => {
+                self.library_view.cycle_view(-1, library);
+            }
+            Some(Action::Up) => {
+                self.library_view.select_previous();
+            }
+            Some(Action::Down) => {
+                self.library_view.select_next();
+            }
+            Some(Action::AddAll) => {
+                // everything that matches
+                let songs = self.library_view.all_songs();
+                self.notify(format!("added {} song(s) to the queue", songs.len()));
+                self.player.enqueue_songs(songs);
+            }
+            Some(Action::Open) => {
+                match self.library_view.selected() {
+                    Some(Item::Song(song)) => {
+                        let song = song.clone();
+                        self.notify(format!("added {} to the queue", song.get_title().unwrap_or("--")));
+                        self.player.enqueue_songs(vec![song]);
+                    }
+                    Some(Item::Group { .. }) => {
+                        self.library_view.open_group(library);
+                    }
+                    None => {}
+                }
+            }
+            _ => {
+                match key_event.code {
+                    KeyCode::Backspace => {
+                        if self.library_view.query.pop().is_some() {
+                            self.library_view.refresh(library);
+                        } else {
+                            self.library_view.close_group(library);
+                        }
+                    }
+                    KeyCode::Char(c) => {
+                        self.library_view.query.push(c);
+                        self.library_view.refresh(library);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Keys for the stats view
+    fn handle_stats_key(&mut self, key_event: KeyEvent) {
+        let plays = &self.player.plays;
+        match self.keys.action(Context::Stats, key_event) {
+            Some(Action::Back) => {
+                self.focus = Focus::Player;
+            }
+            Some(Action::NextView) => {
+                self.stats_view.cycle_category(1, plays);
+            }
+            Some(Action::PreviousView) => {
+                self.stats_view.cycle_category(-1, plays);
+            }
+            Some(Action::NextPeriod) => {
+                self.stats_view.cycle_period(1, plays);
+            }
+            Some(Action::PreviousPeriod) => {
+                self.stats_view.cycle_period(-1, plays);
+            }
+            Some(Action::Up) => {
+                self.stats_view.scroll_up();
+            }
+            Some(Action::Down) => {
+                self.stats_view.scroll_down();
             }
             _ => {}
         }
     }
 
+    /// Keys for the queue while it has focus, returns whether the key was used
+    fn handle_queue_key(&mut self, key_event: KeyEvent) -> bool {
+        let selected = self.selected_in_queue();
+        match self.keys.action(Context::Queue, key_event) {
+            Some(Action::Back) => {
+                self.focus = Focus::Player;
+            }
+            Some(Action::Up) => {
+                self.queue_selected = selected.unwrap_or(0).saturating_sub(1);
+            }
+            Some(Action::Down) => {
+                self.queue_selected = selected.map_or(0, |selected| selected + 1);
+            }
+            Some(Action::MoveUp) => {
+                if let Some(selected) = selected {
+                    self.queue_selected = self.player.move_in_queue(selected, -1);
+                }
+            }
+            Some(Action::MoveDown) => {
+                if let Some(selected) = selected {
+                    self.queue_selected = self.player.move_in_queue(selected, 1);
+                }
+            }
+            Some(Action::Open) => {
+                if let Some(selected) = selected {
+                    self.player.jump_in_queue(selected);
+                    self.queue_selected = 0;
+                }
+            }
+            Some(Action::PlayNext) => {
+                if let Some(selected) = selected {
+                    self.player.play_next(selected);
+                    self.queue_selected = 0;
+                }
+            }
+            Some(Action::Remove) => {
+                if let Some(selected) = selected {
+                    self.player.remove_from_queue(selected);
+                }
+            }
+            Some(Action::Clear) => {
+                self.player.clear_queue();
+                self.queue_selected = 0;
+            }
+            Some(Action::SavePlaylist) => {
+                self.input = Some(Input { prompt: Prompt::SavePlaylist, text: String::new() });
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Keys while a text prompt is open
+    fn handle_input_key(&mut self, key_event: KeyEvent) {
+        let Some(input) = &mut self.input else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Esc => {
+                self.input = None;
+            }
+            KeyCode::Enter => {
+                if let Some(input) = self.input.take() {
+                    self.submit_input(input);
+                }
+            }
+            KeyCode::Backspace => {
+                input.text.pop();
+            }
+            KeyCode::Char(c) => {
+                input.text.push(c);
+            }
+            _ => {}
+        }
+    }
+
+    fn submit_input(&mut self, input: Input) {
+        if input.text.trim().is_empty() {
+            return;
+        }
+        match input.prompt {
+            Prompt::SavePlaylist => {
+                let path = playlist::playlist_path(&input.text, &self.file_picker.dir);
+                match self.player.save_playlist(&path) {
+                    Ok(()) => {
+                        self.notify(format!("saved playlist to {}", path.display()));
+                        self.file_picker.refresh();
+                    }
+                    Err(error) => {
+                        self.notify(format!("couldn't save playlist: {error}"));
+                    }
+                }
+            }
+        }
+    }
+
     fn exit(&mut self) {
         self.exit = true;
     }
 
     fn draw(&self, frame: &mut Frame) {
-        if (frame.area().width < 130) || (frame.area().height < 40) {
+        if too_small(frame.area()) {
             let area = frame.area();
             let text = "Please expand your terminal or zoom out!";
             let text_height = 1;
@@ -450,170 +1707,303 @@ impl App {
             frame.render_widget(
                 Paragraph::new(text)
                     .alignment(Alignment::Center)
-                    .style(Style::default().fg(Color::White)),
+                    .style(Style::default().fg(palette().text)),
                 Rect::new(x + 1, y + 1, text_width + 2, text_height),
             );
             return;
         }
 
-        // create the main block that takes the entire terminal size
-        let main = Rect::new(
-            0,
-            0,
-            frame.area().width,
-            frame.area().height,
-        );
-
+        let panes = panes(frame.area());
+        let main = panes.main;
 
         let main_title = Line::from(vec![
             Span::raw(" doob audio player | "),
             Span::raw("HxW: "),
-            Span::styled(format!("{}x{}", main.width, main.height), Style::default().fg(Color::Yellow)),
+            Span::styled(format!("{}x{}", main.width, main.height), Style::default().fg(palette().value)),
             Span::raw(" | FPS: "),
-            Span::styled(format!("{:.0} ", self.fps), Style::default().fg(Color::Yellow)),
+            Span::styled(format!("{:.0} ", self.fps), Style::default().fg(palette().value)),
         ]);
 
-        frame.render_widget(
-            Block::default()
-                .title(main_title)
-                .title_alignment(Alignment::Center)
-                .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::White)),
-            main,
-        );
+        let mut main_block = Block::default()
+            .title(main_title)
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(palette().text));
+        if let Some((message, shown)) = &self.message {
+            if shown.elapsed() < MESSAGE_DURATION {
+                main_block = main_block.title_bottom(
+                    Line::from(Span::styled(format!(" {message} "), Style::default().fg(palette().highlight))).centered(),
+                );
+            }
+        }
+        frame.render_widget(main_block, main);
 
-        let main_pad = Rect::new(
-            main.x + 2,
-            main.y + 1,
-            main.width.saturating_sub(4),
-            main.height.saturating_sub(2),
+        let block_visualizer = panes.visualizer;
+        frame.render_widget(
+            Paragraph::new(self.visualizer.spectrum(
+                block_visualizer.width.saturating_sub(2) as usize,
+                block_visualizer.height.saturating_sub(2) as usize,
+            )).block(default_block(" Visualizer ")),
+            block_visualizer,
         );
 
+        let block_player = panes.player;
+        if self.focus == Focus::Effects {
+            frame.render_widget(
+                Paragraph::new(self.effects_pane.content(
+                    &self.player.effects.settings(),
+                    block_player.width.saturating_sub(2) as usize,
+                    block_player.height.saturating_sub(2) as usize,
+                )).block(
+                    default_block(" Effects ")
+                        .border_style(Style::default().fg(palette().highlight))
+                        .title_bottom(Line::from(self.keys.hints(Context::Effects, &[
+                            (Action::Down, "select"),
+                            (Action::Increase, "adjust"),
+                            (Action::Reset, "reset"),
+                            (Action::Back, "back"),
+                        ])).centered()),
+                ),
+                block_player,
+            );
+        } else {
+            frame.render_widget(
+                self.player_content(block_player.width as usize, block_player.height as usize)
+                    .block(default_block(" Player ")),
+                block_player,
+            );
+            frame.render_widget(
+                Paragraph::new(self.progress_content(panes.progress.width as usize)),
+                panes.progress,
+            );
+        }
 
-        let [block_top, block_visualizer] = Layout::vertical([
-            Constraint::Length(35),
-            Constraint::Min(1),
-        ]).areas(main_pad);
-
-        frame.render_widget(Block::bordered()
-                                .title(" Visualizer ")
-                                .title_alignment(Alignment::Center), block_visualizer);
-
-        let [block_left, block_player, block_right] = Layout::horizontal([
-            Constraint::Min(1),
-            Constraint::Length(50),
-            Constraint::Min(1),
-        ]).areas(block_top);
-
+        let block_file_picker = panes.file_picker;
+        let block_queue = panes.queue;
+        let focused = self.focus == Focus::FilePicker;
+        let mut file_picker_block = default_block(" File Picker ");
+        if focused {
+            file_picker_block = file_picker_block
+                .border_style(Style::default().fg(palette().highlight))
+                .title_bottom(Line::from(self.keys.hints(Context::FilePicker, &[
+                    (Action::Open, "open"),
+                    (Action::Leave, "up"),
+                    (Action::Replace, "replace"),
+                    (Action::Add, "add"),
+                    (Action::Playlists, "playlists"),
+                ])).centered());
+        }
         frame.render_widget(
-            self.player_content(block_player.width as usize, block_player.height as usize)
-                .block(default_block(" Player ")),
-            block_player,
+            Paragraph::new(self.file_picker.content(
+                block_file_picker.width.saturating_sub(2) as usize,
+                block_file_picker.height.saturating_sub(2) as usize,
+                focused,
+            )).block(file_picker_block),
+            block_file_picker,
         );
+        if self.show_lyrics {
+            let title = match self.lyrics.source {
+                "" => " Lyrics ".to_string(),
+                source => format!(" Lyrics ({source}) "),
+            };
+            frame.render_widget(
+                Paragraph::new(self.lyrics.content(
+                    self.player.get_position(),
+                    self.player.get_duration(),
+                    block_queue.width.saturating_sub(2) as usize,
+                    block_queue.height.saturating_sub(2) as usize,
+                )).alignment(Alignment::Center).block(default_block(&title)),
+                block_queue,
+            );
+        } else {
+            let mut queue_block = default_block(" Queue ");
+            if self.focus == Focus::Queue {
+                queue_block = queue_block
+                    .border_style(Style::default().fg(palette().highlight))
+                    .title_bottom(Line::from(self.keys.hints(Context::Queue, &[
+                        (Action::Open, "jump"),
+                        (Action::PlayNext, "play next"),
+                        (Action::MoveUp, "move up"),
+                        (Action::MoveDown, "move down"),
+                        (Action::Remove, "remove"),
+                        (Action::Clear, "clear"),
+                        (Action::SavePlaylist, "save"),
+                    ])).centered());
+            }
+            frame.render_widget(
+                self.queue_content(block_queue.width as usize, block_queue.height as usize).block(queue_block),
+                block_queue,
+            );
+        }
 
-        let [block_file_picker, block_queue] = Layout::vertical([
-            Constraint::Percentage(20),
-            Constraint::Percentage(80),
-        ]).areas(block_left);
-        frame.render_widget(Block::bordered()
-                                .title(" File Picker ")
-                                .title_alignment(Alignment::Center), block_file_picker);
         frame.render_widget(
-            self.queue_content(block_queue.width as usize, block_queue.height as usize)
-                .block(default_block(" Queue ")),
-            block_queue,
+            Paragraph::new(self.cover.content()).block(default_block(" Cover ")),
+            panes.cover,
         );
 
-        let [block_telly, block_osc] = Layout::vertical([
-            Constraint::Percentage(50),
-            Constraint::Percentage(50),
-        ]).areas(block_right);
-
-        frame.render_widget(Block::bordered()
-                                .title(" Oscilloscope ")
-                                .title_alignment(Alignment::Center), block_osc);
-
-        let sine_wave = self.generate_sine_wave(
-            block_telly.width as usize - 2,
-            block_telly.height as usize - 2
+        let block_telly = panes.telly;
+        let block_osc = panes.osc;
+        frame.render_widget(
+            Paragraph::new(self.visualizer.oscilloscope(
+                block_osc.width.saturating_sub(2) as usize,
+                block_osc.height.saturating_sub(2) as usize,
+            )).block(default_block(" Oscilloscope ")),
+            block_osc,
         );
+
         frame.render_widget(
-            Paragraph::new(sine_wave)
-                .alignment(Alignment::Left)
-                .style(Style::default().fg(Color::White))
-                .block(default_block(" Meow ")),
+            Paragraph::new(self.visualizer.levels(
+                block_telly.width.saturating_sub(2) as usize,
+                block_telly.height.saturating_sub(2) as usize,
+            )).block(default_block(" Meow ")),
             block_telly,
         );
-    }
 
-    fn queue_content(&self, width: usize, height: usize) -> Paragraph {
-        // loop playlist, add current song to end
-        // loop song, show playing song as next
+        if self.focus == Focus::Library {
+            let area = Rect::new(
+                main.x + main.width / 10,
+                main.y + main.height / 10,
+                main.width - main.width / 5,
+                main.height - main.height / 5,
+            );
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(self.library_view.content(
+                    area.width.saturating_sub(2) as usize,
+                    area.height.saturating_sub(2) as usize,
+                )).block(
+                    default_block(" Library ")
+                        .border_style(Style::default().fg(palette().highlight))
+                        .title_bottom(Line::from(self.keys.hints(Context::Library, &[
+                            (Action::NextView, "view"),
+                            (Action::Open, "add/open"),
+                            (Action::AddAll, "add all"),
+                            (Action::Back, "back"),
+                        ])).centered()),
+                ),
+                area,
+            );
+        }
 
-        // let mut queue = &self.player.queue;
-        let mut local_queue = self.player.queue.clone();
-        let mut lines = Vec::new();
-        match self.player.loop_type {
-            LoopType::None => {
-                let pad = (width - 10) / 2;
-                let line = Line::from(vec![
-                    Span::styled(format!("{:pad$}{}", "", "Loop: ", pad = pad), Style::default().fg(Color::LightRed)),
-                    Span::styled("None", Style::default().fg(Color::LightYellow)),
-                ]);
-                lines.push(line);
-            }
-            LoopType::Loop => {
-                let pad = (width - 14) / 2;
-                let line = Line::from(vec![
-                    Span::styled(format!("{:pad$}{}", "", "Loop: ", pad = pad), Style::default().fg(Color::LightRed)),
-                    Span::styled("Playlist", Style::default().fg(Color::LightYellow)),
-                ]);
-                lines.push(line);
-            }
-            LoopType::LoopOne => {
-                let pad = (width - 10) / 2;
-                let line = Line::from(vec![
-                    Span::styled(format!("{:pad$}{}", "", "Loop: ", pad = pad), Style::default().fg(Color::LightRed)),
-                    Span::styled("Song", Style::default().fg(Color::LightYellow)),
-                ]);
-                lines.push(line);
-
-                // clear the local queue and add the current song only
-                local_queue.clear();
-                if let Some(song) = &self.player.current_song {
-                    local_queue.push_back(song.clone());
-                }
-            }
+        if self.focus == Focus::Stats {
+            let area = Rect::new(
+                main.x + main.width / 10,
+                main.y + main.height / 10,
+                main.width - main.width / 5,
+                main.height - main.height / 5,
+            );
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(self.stats_view.content(
+                    area.width.saturating_sub(2) as usize,
+                    area.height.saturating_sub(2) as usize,
+                )).block(
+                    default_block(" Stats ")
+                        .border_style(Style::default().fg(palette().highlight))
+                        .title_bottom(Line::from(self.keys.hints(Context::Stats, &[
+                            (Action::NextView, "top"),
+                            (Action::NextPeriod, "period"),
+                            (Action::Back, "back"),
+                        ])).centered()),
+                ),
+                area,
+            );
         }
 
-        let max = 22; // max num of songs to show
-        for (i, song) in local_queue.iter().enumerate() {
-            if i >= max {
-                lines.push(Line::from(Span::styled("      ...", Style::default().fg(Color::LightCyan))));
-                break;
-            }
+        if let Some(scroll) = self.help {
+            let area = Rect::new(
+                main.x + main.width / 6,
+                main.y + main.height / 10,
+                main.width - main.width / 3,
+                main.height - main.height / 5,
+            );
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(self.keys.help(scroll, area.height.saturating_sub(2) as usize)).block(
+                    default_block(" Keys ")
+                        .border_style(Style::default().fg(palette().highlight))
+                        .title_bottom(Line::from(" ↑↓ scroll | any other key closes ").centered()),
+                ),
+                area,
+            );
+        }
 
+        if let Some(input) = &self.input {
+            let width = (main.width / 2).max(60).min(main.width);
+            let area = Rect::new(main.x + (main.width - width) / 2, main.y + main.height / 2 - 1, width, 3);
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(Line::from(vec![
+                    Span::raw(" "),
+                    Span::styled(input.text.clone(), Style::default().fg(palette().value)),
+                    Span::styled("▏", Style::default().fg(palette().highlight)),
+                ])).block(default_block(input.prompt.title()).border_style(Style::default().fg(palette().highlight))),
+                area,
+            );
+        }
+    }
+
+    fn queue_content(&self, width: usize, height: usize) -> Paragraph {
+        let mut lines = Vec::new();
+        let loop_type = match self.player.loop_type {
+            LoopType::None => "None",
+            LoopType::Loop => "Playlist",
+            LoopType::LoopOne => "Song",
+        };
+        let pad = width.saturating_sub(6 + loop_type.len()) / 2;
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:pad$}{}", "", "Loop: ", pad = pad), Style::default().fg(palette().accent)),
+            Span::styled(loop_type, Style::default().fg(palette().highlight)),
+        ]));
+        lines.push(Line::from(Span::raw(" ")));
+
+        let upcoming = self.player.upcoming();
+        let (start, rows) = self.queue_rows(height);
+        let selected = self.selected_in_queue().filter(|_| self.focus == Focus::Queue);
+        for (i, song) in upcoming.iter().enumerate().skip(start).take(rows) {
             let title = song.get_title().unwrap_or("--").to_string();
             let artist = song.get_artist().unwrap_or("--").to_string();
-
-            if i == 0 {
-                lines.push(Line::from(Span::raw(" ")));
-                let line = Line::from(vec![
-                    Span::styled("   Next: ", Style::default().fg(Color::LightRed)),
-                    Span::styled(title, Style::default().fg(Color::LightCyan)),
-                    Span::styled(" - ", Style::default().fg(Color::White)),
-                    Span::styled(artist, Style::default().fg(Color::LightYellow)),
-                ]);
-                lines.push(line);
-                lines.push(Line::from(Span::raw(" ")));
+            // looping a song, the first one in the queue only comes after skipping it
+            let number = if i == 0 && !matches!(self.player.loop_type, LoopType::LoopOne) {
+                " Next: ".to_string()
+            } else {
+                format!("{:5}. ", i + 1)
+            };
+            let mut spans = vec![
+                Span::styled(number, Style::default().fg(palette().accent)),
+                Span::styled(title, Style::default().fg(palette().title)),
+                Span::styled(" - ", Style::default().fg(palette().text)),
+                Span::styled(artist, Style::default().fg(palette().highlight)),
+            ];
+            if selected == Some(i) {
+                spans.insert(0, Span::styled(">", Style::default().fg(palette().highlight)));
+                for span in spans.iter_mut().skip(1) {
+                    span.style = span.style.bg(palette().dim).add_modifier(Modifier::BOLD);
+                }
             } else {
-                let line = Line::from(vec![
-                    Span::styled(format!("  {:2}. ", i + 1), Style::default().fg(Color::LightRed)),
-                    Span::styled(title, Style::default().fg(Color::LightCyan)),
-                    Span::styled(" - ", Style::default().fg(Color::White)),
-                    Span::styled(artist, Style::default().fg(Color::LightYellow)),
-                ]);
-                lines.push(line);
+                spans.insert(0, Span::raw(" "));
+            }
+            lines.push(Line::from(spans));
+        }
+        if upcoming.is_empty() {
+            lines.push(Line::from(Span::styled(
+                format!("{:pad$}queue is empty", "", pad = width.saturating_sub(16) / 2),
+                Style::default().fg(palette().dim),
+            )));
+        }
+
+        if !self.player.errors.is_empty() {
+            lines.push(Line::from(Span::raw(" ")));
+            lines.push(Line::from(Span::styled(
+                format!("  {} file(s) couldn't be decoded:", self.player.errors.len()),
+                Style::default().fg(palette().accent),
+            )));
+            for error in self.player.errors.iter().take(3) {
+                let name = error.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+                lines.push(Line::from(vec![
+                    Span::styled(format!("    {name}: "), Style::default().fg(palette().text)),
+                    Span::styled(error.error.clone(), Style::default().fg(palette().error)),
+                ]));
             }
         }
 
@@ -621,6 +2011,53 @@ impl App {
             .alignment(Alignment::Left)
     }
 
+    /// Selected queue entry, kept inside the queue as songs are taken from it
+    fn selected_in_queue(&self) -> Option<usize> {
+        let len = self.player.upcoming().len();
+        (len > 0).then(|| self.queue_selected.min(len - 1))
+    }
+
+    /// First queue entry shown and how many fit in the pane, keeping the selection in the middle
+    fn queue_rows(&self, height: usize) -> (usize, usize) {
+        let errors = if self.player.errors.is_empty() { 0 } else { 2 + self.player.errors.len().min(3) };
+        // borders, the loop line and the gap under it
+        let rows = height.saturating_sub(4 + errors).max(1);
+        let len = self.player.upcoming().len();
+        let selected = self.selected_in_queue().unwrap_or(0);
+        (selected.saturating_sub(rows / 2).min(len.saturating_sub(rows)), rows)
+    }
+
+    /// Progress bar with the elapsed and total time, clicking it seeks
+    fn progress_content(&self, width: usize) -> Line<'static> {
+        let position = self.player.get_position();
+        let duration = self.player.get_duration();
+        let elapsed = format_time(position);
+        let total = format_time(duration);
+        let bar_width = width.saturating_sub(elapsed.len() + total.len() + 2);
+        let fraction = if duration > 0.0 { (position / duration).clamp(0.0, 1.0) } else { 0.0 };
+        let filled = ((fraction * bar_width as f64).round() as usize).min(bar_width.saturating_sub(1));
+
+        Line::from(vec![
+            Span::styled(format!("{elapsed} "), Style::default().fg(palette().value)),
+            Span::styled("━".repeat(filled), Style::default().fg(palette().special)),
+            Span::styled(if bar_width > 0 { "●" } else { "" }, Style::default().fg(palette().special)),
+            Span::styled("─".repeat(bar_width.saturating_sub(filled + 1)), Style::default().fg(palette().dim)),
+            Span::styled(format!(" {total}"), Style::default().fg(palette().value)),
+        ])
+    }
+
+    /// Fraction of the song at a column of the progress bar, if the column is on the bar
+    fn progress_fraction(&self, area: Rect, column: u16) -> Option<f64> {
+        let elapsed = format_time(self.player.get_position()).len() as u16 + 1;
+        let total = format_time(self.player.get_duration()).len() as u16 + 1;
+        let start = area.x + elapsed;
+        let bar_width = area.width.saturating_sub(elapsed + total);
+        if bar_width == 0 || column < start || column >= start + bar_width {
+            return None;
+        }
+        Some((column - start) as f64 / (bar_width - 1).max(1) as f64)
+    }
+
     fn player_content(&self, width: usize, height: usize) -> Paragraph {
         let position = format!("{:.2}", self.player.get_position());
         let duration = format!("{:.2}", self.player.get_duration());
@@ -632,12 +2069,27 @@ impl App {
         }
         let face = if self.player.is_playing() { "d-_-b" } else { "do_ob" };
         let speed = format!("{:.2}", self.player.get_playback_speed());
+        let pitch = format!("{:+} st", self.player.pitch);
+        let keep_pitch = self.player.keep_pitch.to_string();
+        let seek_step = format!("{}s", self.player.seek_step);
+        let crossfade = match self.player.crossfade {
+            0.0 => "off".to_string(),
+            crossfade => format!("{crossfade}s"),
+        };
+        let gain = match self.player.gain_mode {
+            GainMode::Off => "off".to_string(),
+            mode => format!("{} ({:+.1} dB)", mode.name(), 20.0 * self.player.gain.get().log10()),
+        };
         let loop_type = match self.player.loop_type {
             LoopType::None => "none",
             LoopType::Loop => "loop",
             LoopType::LoopOne => "loop_one",
         };
-        let shuffle = self.player.shuffle.to_string();
+        let shuffle = match (self.player.shuffle, self.player.smart_shuffle) {
+            (false, _) => "false",
+            (true, false) => "true",
+            (true, true) => "smart",
+        };
         let mut sample_rate = "--".to_string();
         let mut channels = "--".to_string();
         let mut title = "--";
@@ -654,75 +2106,95 @@ impl App {
             year = self.player.current_song.as_ref().unwrap().get_year().unwrap_or("--");
         }
 
-        let queue = self.player.queue.len().to_string();
+        let queue = self.player.upcoming().len().to_string();
 
         let content_lines: Vec<Line> = vec![
             // Line::from(Span::raw("test")),
-            Line::from(Span::styled(face, Style::default().fg(Color::LightMagenta))),
+            Line::from(Span::styled(face, Style::default().fg(palette().special))),
             Line::from(Span::raw(" ")),
             Line::from(vec![
-                Span::styled("position: ", Style::default().fg(Color::White)),
-                Span::styled(position, Style::default().fg(Color::Yellow)),
+                Span::styled("position: ", Style::default().fg(palette().text)),
+                Span::styled(position, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("duration: ", Style::default().fg(Color::White)),
-                Span::styled(duration, Style::default().fg(Color::Yellow)),
+                Span::styled("duration: ", Style::default().fg(palette().text)),
+                Span::styled(duration, Style::default().fg(palette().value)),
             ]),
             Line::from(Span::raw(" ")),
             Line::from(vec![
-                Span::styled("playing: ", Style::default().fg(Color::White)),
-                Span::styled(playing, Style::default().fg(Color::Yellow)),
+                Span::styled("playing: ", Style::default().fg(palette().text)),
+                Span::styled(playing, Style::default().fg(palette().value)),
+            ]),
+            Line::from(vec![
+                Span::styled("volume: ", Style::default().fg(palette().text)),
+                Span::styled(volume, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("volume: ", Style::default().fg(Color::White)),
-                Span::styled(volume, Style::default().fg(Color::Yellow)),
+                Span::styled("speed: ", Style::default().fg(palette().text)),
+                Span::styled(speed, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("speed: ", Style::default().fg(Color::White)),
-                Span::styled(speed, Style::default().fg(Color::Yellow)),
+                Span::styled("pitch: ", Style::default().fg(palette().text)),
+                Span::styled(pitch, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("loop type: ", Style::default().fg(Color::White)),
-                Span::styled(loop_type, Style::default().fg(Color::Yellow)),
+                Span::styled("keep pitch: ", Style::default().fg(palette().text)),
+                Span::styled(keep_pitch, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("shuffle: ", Style::default().fg(Color::White)),
-                Span::styled(shuffle, Style::default().fg(Color::Yellow)),
+                Span::styled("seek step: ", Style::default().fg(palette().text)),
+                Span::styled(seek_step, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("folder: ", Style::default().fg(Color::White)),
-                Span::styled(folder, Style::default().fg(Color::Yellow)),
+                Span::styled("crossfade: ", Style::default().fg(palette().text)),
+                Span::styled(crossfade, Style::default().fg(palette().value)),
+            ]),
+            Line::from(vec![
+                Span::styled("normalize: ", Style::default().fg(palette().text)),
+                Span::styled(gain, Style::default().fg(palette().value)),
+            ]),
+            Line::from(vec![
+                Span::styled("loop type: ", Style::default().fg(palette().text)),
+                Span::styled(loop_type, Style::default().fg(palette().value)),
+            ]),
+            Line::from(vec![
+                Span::styled("shuffle: ", Style::default().fg(palette().text)),
+                Span::styled(shuffle, Style::default().fg(palette().value)),
+            ]),
+            Line::from(vec![
+                Span::styled("folder: ", Style::default().fg(palette().text)),
+                Span::styled(folder, Style::default().fg(palette().value)),
             ]),
             Line::from(Span::raw(" ")),
             Line::from(vec![
-                Span::styled("sample rate: ", Style::default().fg(Color::White)),
-                Span::styled(sample_rate, Style::default().fg(Color::Yellow)),
+                Span::styled("sample rate: ", Style::default().fg(palette().text)),
+                Span::styled(sample_rate, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("channels: ", Style::default().fg(Color::White)),
-                Span::styled(channels, Style::default().fg(Color::Yellow)),
+                Span::styled("channels: ", Style::default().fg(palette().text)),
+                Span::styled(channels, Style::default().fg(palette().value)),
             ]),
             Line::from(Span::raw(" ")),
             Line::from(vec![
-                Span::styled("title: ", Style::default().fg(Color::White)),
-                Span::styled(title, Style::default().fg(Color::Yellow)),
+                Span::styled("title: ", Style::default().fg(palette().text)),
+                Span::styled(title, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("artist: ", Style::default().fg(Color::White)),
-                Span::styled(artist, Style::default().fg(Color::Yellow)),
+                Span::styled("artist: ", Style::default().fg(palette().text)),
+                Span::styled(artist, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("album: ", Style::default().fg(Color::White)),
-                Span::styled(album, Style::default().fg(Color::Yellow)),
+                Span::styled("album: ", Style::default().fg(palette().text)),
+                Span::styled(album, Style::default().fg(palette().value)),
             ]),
             Line::from(vec![
-                Span::styled("year: ", Style::default().fg(Color::White)),
-                Span::styled(year, Style::default().fg(Color::Yellow)),
+                Span::styled("year: ", Style::default().fg(palette().text)),
+                Span::styled(year, Style::default().fg(palette().value)),
             ]),
             Line::from(Span::raw(" ")),
             Line::from(vec![
-                Span::styled("queue length: ", Style::default().fg(Color::White)),
-                Span::styled(queue, Style::default().fg(Color::Yellow)),
+                Span::styled("queue length: ", Style::default().fg(palette().text)),
+                Span::styled(queue, Style::default().fg(palette().value)),
             ]),
             Line::from(Span::raw("⏵ ⏸ ⏹ ⏭ ⏮ ⏴ ⏪ ⏩ 🔀 🔁 🔂 🔄")),
         ];
@@ -741,26 +2213,88 @@ impl App {
         Paragraph::new(Text { lines, ..Default::default() })
             .alignment(Alignment::Center)
     }
+}
 
-    fn generate_sine_wave(&self, width: usize, height: usize) -> String {
-        let time = self.start_time.elapsed().as_secs_f64() * 1.5;
-        let mut wave = String::new();
-        let frequency = 1.0 * std::f64::consts::PI / width as f64;
+/// Areas of every pane, shared by drawing and mouse handling
+struct Panes {
+    main: Rect,
+    visualizer: Rect,
+    player: Rect,
+    /// Progress bar row inside the player pane
+    progress: Rect,
+    file_picker: Rect,
+    queue: Rect,
+    cover: Rect,
+    telly: Rect,
+    osc: Rect,
+}
 
-        for y in 0..height {
-            for x in 0..width {
-                let value = (x as f64 * frequency + time).sin();
-                let scaled_value = ((value + 1.0) * (height as f64 / 2.3)).round() as usize;
-                if scaled_value == y {
-                    wave.push('#');
-                } else {
-                    wave.push(' ');
-                }
-            }
-            wave.push('\n');
-        }
-        wave
-    }
+fn too_small(area: Rect) -> bool {
+    (area.width < 130) || (area.height < 40)
+}
+
+fn panes(area: Rect) -> Panes {
+    // create the main block that takes the entire terminal size
+    let main = Rect::new(0, 0, area.width, area.height);
+    let main_pad = Rect::new(
+        main.x + 2,
+        main.y + 1,
+        main.width.saturating_sub(4),
+        main.height.saturating_sub(2),
+    );
+
+    let [block_top, visualizer] = Layout::vertical([
+        Constraint::Length(35),
+        Constraint::Min(1),
+    ]).areas(main_pad);
+
+    let [block_left, player, block_right] = Layout::horizontal([
+        Constraint::Min(1),
+        Constraint::Length(50),
+        Constraint::Min(1),
+    ]).areas(block_top);
+
+    let [file_picker, queue] = Layout::vertical([
+        Constraint::Percentage(20),
+        Constraint::Percentage(80),
+    ]).areas(block_left);
+
+    // square cover (two pixels per cell), but leaving room for the meters
+    let cover_height = (block_right.width.saturating_sub(2) / 2 + 2).min(block_right.height / 2);
+    let [cover, block_meters] = Layout::vertical([
+        Constraint::Length(cover_height),
+        Constraint::Min(1),
+    ]).areas(block_right);
+
+    let [telly, osc] = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Percentage(60),
+    ]).areas(block_meters);
+
+    // second to last row inside the player border
+    let progress = Rect::new(
+        player.x + 2,
+        (player.y + player.height).saturating_sub(3),
+        player.width.saturating_sub(4),
+        1,
+    );
+
+    Panes { main, visualizer, player, progress, file_picker, queue, cover, telly, osc }
+}
+
+/// Formats seconds as m:ss
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Directory for files that persist between runs
+fn data_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("rat-slime")
 }
 
 fn default_block(title: &str) -> Block {
@@ -775,11 +2309,8 @@ todo
 - limit to 175x50?
 - add cool background if bigger than that
 - queue right hand padding, maybe cut off name to write artist?
-- add album cover
 
 known issues
-- switch loop back to playlist doesn't add old songs
-- switching speed doesnt update position right
 - audio stuttering???
 
 
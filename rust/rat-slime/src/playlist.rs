@@ -0,0 +1,162 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::loudness::GainMode;
+use crate::{data_dir, LoopType, Song};
+
+/// Whether a file is an m3u/m3u8 playlist
+pub fn is_playlist_file(path: &Path) -> bool {
+    let extension = path.extension().and_then(|s| s.to_str()).map(str::to_lowercase);
+    matches!(extension.as_deref(), Some("m3u") | Some("m3u8"))
+}
+
+/// Reads the tracks of an m3u/m3u8 playlist, relative paths are relative to the playlist's folder
+pub fn read_m3u(path: &Path) -> io::Result<Vec<PathBuf>> {
+    // m3u files are often latin-1, lossy decoding keeps the ascii paths working at least
+    let bytes = fs::read(path)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let base = path.parent().unwrap_or(Path::new("."));
+
+    let mut tracks = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        // #EXTM3U, #EXTINF and other directives
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("file://").unwrap_or(line);
+        // windows style separators from playlists made elsewhere
+        let track = PathBuf::from(line.replace('\\', "/"));
+        if track.is_absolute() {
+            tracks.push(track);
+        } else {
+            tracks.push(base.join(track));
+        }
+    }
+    Ok(tracks)
+}
+
+/// Writes songs to an m3u8 playlist, with paths relative to the playlist where possible
+pub fn write_m3u(path: &Path, songs: &[&Song]) -> io::Result<()> {
+    let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let base = fs::canonicalize(&base).unwrap_or(base);
+    let mut text = String::from("#EXTM3U\n");
+    for song in songs {
+        text += &format!(
+            "#EXTINF:{},{} - {}\n",
+            song.duration.as_secs(),
+            song.get_artist().unwrap_or("--"),
+            song.get_title().unwrap_or("--"),
+        );
+        let track = fs::canonicalize(&song.path).unwrap_or(song.path.clone());
+        let track = relative_path(&base, &track).unwrap_or(track);
+        text += &format!("{}\n", track.to_string_lossy());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, text)
+}
+
+/// Path of `to` relative to the directory `from`, None if they don't share a root
+fn relative_path(from: &Path, to: &Path) -> Option<PathBuf> {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    // different roots/prefixes (e.g. drives) can't be made relative
+    if from.first() != to.first() {
+        return None;
+    }
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component.as_os_str());
+    }
+    Some(relative)
+}
+
+/// Directory the named playlists are saved in
+pub fn playlists_dir() -> PathBuf {
+    data_dir().join("playlists")
+}
+
+/// Where a playlist name points: a path if it looks like one, otherwise a named playlist
+pub fn playlist_path(name: &str, relative_to: &Path) -> PathBuf {
+    let name = name.trim();
+    if name.contains('/') || is_playlist_file(Path::new(name)) {
+        let path = PathBuf::from(name);
+        let path = if path.is_absolute() { path } else { relative_to.join(path) };
+        if is_playlist_file(&path) { path } else { path.with_extension("m3u8") }
+    } else {
+        playlists_dir().join(format!("{name}.m3u8"))
+    }
+}
+
+/// Queue and playback state, restored on the next start
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Folder the queue was last loaded from
+    pub folder: String,
+    pub current: Option<PathBuf>,
+    /// Position in the current song, in seconds
+    pub position: f64,
+    pub queue: Vec<PathBuf>,
+    /// Songs already played this time round, they come back when looping
+    pub played: Vec<PathBuf>,
+    /// Songs in the order they were added, to undo shuffling
+    pub order: Vec<PathBuf>,
+    pub shuffle: bool,
+    pub smart_shuffle: bool,
+    pub loop_type: LoopType,
+    pub volume: f32,
+    pub playback_speed: f32,
+    pub crossfade: f64,
+    pub gain_mode: GainMode,
+    pub keep_pitch: bool,
+    /// Pitch shift, in semitones
+    pub pitch: f32,
+}
+
+impl Default for Session {
+    /// The same as a fresh start of the player
+    fn default() -> Self {
+        Session {
+            folder: String::new(),
+            current: None,
+            position: 0.0,
+            queue: Vec::new(),
+            played: Vec::new(),
+            order: Vec::new(),
+            shuffle: false,
+            smart_shuffle: false,
+            loop_type: LoopType::default(),
+            volume: 0.05,
+            playback_speed: 1.0,
+            crossfade: 0.0,
+            gain_mode: GainMode::default(),
+            keep_pitch: false,
+            pitch: 0.0,
+        }
+    }
+}
+
+impl Session {
+    fn path() -> PathBuf {
+        data_dir().join("session.json")
+    }
+
+    pub fn load() -> Option<Session> {
+        let text = fs::read_to_string(Session::path()).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(data_dir())?;
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(Session::path(), text)
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::{data_dir, Song};
+
+/// Seconds short of the end (on top of the crossfade) a song can be left at and still count as played through
+const SKIP_MARGIN: f64 = 5.0;
+/// Longest step between two updates that still counts as playing, at double speed on a slow frame
+const MAX_STEP: f64 = 2.0;
+
+/// One time a song was played
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Play {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// When it started, in seconds since the unix epoch
+    pub started: u64,
+    /// Seconds of the song that were actually played, seeking over parts doesn't count them
+    pub heard: f64,
+    pub duration: f64,
+    /// Whether it was left before the end
+    pub skipped: bool,
+}
+
+/// The song playing now, becomes a Play once it stops
+pub struct Listening {
+    song: Song,
+    started: SystemTime,
+    heard: f64,
+    /// Position at the last update, to tell playing apart from seeking
+    position: f64,
+}
+
+impl Listening {
+    pub fn new(song: &Song) -> Listening {
+        Listening { song: song.clone(), started: SystemTime::now(), heard: 0.0, position: 0.0 }
+    }
+
+    /// Counts what was played since the last update, jumps are seeks and don't count
+    pub fn update(&mut self, position: f64, playing: bool) {
+        let step = position - self.position;
+        if playing && step > 0.0 && step < MAX_STEP {
+            self.heard += step;
+        }
+        self.position = position;
+    }
+
+    /// The finished play, None if none of it was heard
+    pub fn finish(self, crossfade: f64) -> Option<Play> {
+        if self.heard <= 0.0 {
+            return None;
+        }
+        let duration = self.song.duration.as_secs_f64();
+        Some(Play {
+            path: self.song.path.clone(),
+            title: self.song.get_title().map(str::to_string),
+            artist: self.song.get_artist().map(str::to_string),
+            album: self.song.get_album().map(str::to_string),
+            started: self.started.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs()),
+            heard: self.heard,
+            duration,
+            skipped: duration > 0.0 && self.position < duration - crossfade - SKIP_MARGIN,
+        })
+    }
+}
+
+/// Every play so far, kept as a line of JSON each so recording one only appends to the file
+pub struct PlayLog {
+    plays: Vec<Play>,
+}
+
+impl PlayLog {
+    fn path() -> PathBuf {
+        data_dir().join("plays.jsonl")
+    }
+
+    /// Reads the log, lines that can't be read (like one cut off by a crash) are left out
+    pub fn open() -> PlayLog {
+        let plays = fs::read_to_string(PlayLog::path())
+            .map(|text| text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+            .unwrap_or_default();
+        PlayLog { plays }
+    }
+
+    pub fn record(&mut self, play: Play) -> io::Result<()> {
+        let line = serde_json::to_string(&play)?;
+        self.plays.push(play);
+        fs::create_dir_all(data_dir())?;
+        let mut file = OpenOptions::new().create(true).append(true).open(PlayLog::path())?;
+        writeln!(file, "{line}")
+    }
+
+    pub fn plays(&self) -> &[Play] {
+        &self.plays
+    }
+
+    /// How likely each song is to be listened to, from how often it was played through and skipped
+    ///
+    /// Songs that were never played are in the middle at 0.5, so new songs still come up.
+    pub fn weights(&self) -> HashMap<&Path, f64> {
+        let mut counts: HashMap<&Path, (u32, u32)> = HashMap::new();
+        for play in &self.plays {
+            let (finished, skipped) = counts.entry(play.path.as_path()).or_default();
+            if play.skipped {
+                *skipped += 1;
+            } else {
+                *finished += 1;
+            }
+        }
+        counts
+            .into_iter()
+            .map(|(path, (finished, skipped))| (path, (finished as f64 + 1.0) / ((finished + skipped) as f64 + 2.0)))
+            .collect()
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+};
+use crate::config::palette;
+use crate::plays::PlayLog;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Category {
+    Tracks,
+    Artists,
+    Albums,
+}
+
+impl Category {
+    const ALL: [Category; 3] = [Category::Tracks, Category::Artists, Category::Albums];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Category::Tracks => "Tracks",
+            Category::Artists => "Artists",
+            Category::Albums => "Albums",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Period {
+    Week,
+    Month,
+    Year,
+    All,
+}
+
+impl Period {
+    const ALL: [Period; 4] = [Period::Week, Period::Month, Period::Year, Period::All];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Period::Week => "7 days",
+            Period::Month => "30 days",
+            Period::Year => "year",
+            Period::All => "all time",
+        }
+    }
+
+    /// Length of the period in seconds, None for all time
+    fn seconds(&self) -> Option<u64> {
+        match self {
+            Period::Week => Some(7 * 24 * 3600),
+            Period::Month => Some(30 * 24 * 3600),
+            Period::Year => Some(365 * 24 * 3600),
+            Period::All => None,
+        }
+    }
+}
+
+/// A track, artist or album with what was played of it
+struct Row {
+    name: String,
+    /// Artist of a track
+    detail: Option<String>,
+    /// Times it was played through
+    plays: u32,
+    skips: u32,
+    /// Seconds listened to
+    heard: f64,
+}
+
+/// Top tracks, artists and albums from the play log
+pub struct StatsView {
+    pub category: Category,
+    pub period: Period,
+    rows: Vec<Row>,
+    /// Seconds listened to in the period, over every play
+    total_heard: f64,
+    total_plays: usize,
+    total_skips: usize,
+    /// First row shown
+    scroll: usize,
+}
+
+impl StatsView {
+    pub fn new() -> Self {
+        Self {
+            category: Category::Tracks,
+            period: Period::Month,
+            rows: Vec::new(),
+            total_heard: 0.0,
+            total_plays: 0,
+            total_skips: 0,
+            scroll: 0,
+        }
+    }
+
+    /// Counts the plays in the period again
+    pub fn refresh(&mut self, log: &PlayLog) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        let since = self.period.seconds().map_or(0, |seconds| now.saturating_sub(seconds));
+        let plays: Vec<_> = log.plays().iter().filter(|play| play.started >= since).collect();
+
+        self.total_heard = plays.iter().map(|play| play.heard).sum();
+        self.total_plays = plays.len();
+        self.total_skips = plays.iter().filter(|play| play.skipped).count();
+
+        let mut rows: HashMap<String, Row> = HashMap::new();
+        for play in plays {
+            let artist = play.artist.clone().unwrap_or_else(|| "Unknown artist".to_string());
+            let (key, name, detail) = match self.category {
+                Category::Tracks => {
+                    let name = play.title.clone().unwrap_or_else(|| {
+                        play.path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+                    });
+                    (play.path.to_string_lossy().to_string(), name, Some(artist))
+                }
+                Category::Artists => (artist.clone(), artist, None),
+                Category::Albums => {
+                    let name = format!("{} - {artist}", play.album.as_deref().unwrap_or("Unknown album"));
+                    (name.clone(), name, None)
+                }
+            };
+            let row = rows.entry(key).or_insert(Row { name, detail, plays: 0, skips: 0, heard: 0.0 });
+            if play.skipped {
+                row.skips += 1;
+            } else {
+                row.plays += 1;
+            }
+            row.heard += play.heard;
+        }
+        self.rows = rows.into_values().collect();
+        self.rows.sort_by(|a, b| {
+            b.plays.cmp(&a.plays).then(b.heard.partial_cmp(&a.heard).unwrap_or(std::cmp::Ordering::Equal))
+        });
+        self.scroll = 0;
+    }
+
+    /// Switches to the next (1) or previous (-1) category
+    pub fn cycle_category(&mut self, direction: i32, log: &PlayLog) {
+        let index = Category::ALL.iter().position(|&category| category == self.category).unwrap_or(0) as i32;
+        let index = (index + direction).rem_euclid(Category::ALL.len() as i32);
+        self.category = Category::ALL[index as usize];
+        self.refresh(log);
+    }
+
+    /// Switches to the next (1) or previous (-1) period
+    pub fn cycle_period(&mut self, direction: i32, log: &PlayLog) {
+        let index = Period::ALL.iter().position(|&period| period == self.period).unwrap_or(0) as i32;
+        let index = (index + direction).rem_euclid(Period::ALL.len() as i32);
+        self.period = Period::ALL[index as usize];
+        self.refresh(log);
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.scroll + 1 < self.rows.len() {
+            self.scroll += 1;
+        }
+    }
+
+    pub fn content(&self, width: usize, height: usize) -> Text<'static> {
+        let palette = palette();
+        let tab = |selected: bool| {
+            if selected {
+                Style::default().fg(palette.highlight).add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            } else {
+                Style::default().fg(palette.dim)
+            }
+        };
+        let mut lines = Vec::new();
+
+        let mut tabs = vec![Span::raw(" ")];
+        for category in Category::ALL {
+            tabs.push(Span::styled(category.name(), tab(category == self.category)));
+            tabs.push(Span::raw("   "));
+        }
+        lines.push(Line::from(tabs));
+        let mut periods = vec![Span::styled(" period: ", Style::default().fg(palette.accent))];
+        for period in Period::ALL {
+            periods.push(Span::styled(period.name(), tab(period == self.period)));
+            periods.push(Span::raw("  "));
+        }
+        lines.push(Line::from(periods));
+        lines.push(Line::from(vec![
+            Span::styled(" listened: ", Style::default().fg(palette.text)),
+            Span::styled(format_hours(self.total_heard), Style::default().fg(palette.value)),
+            Span::styled("   plays: ", Style::default().fg(palette.text)),
+            Span::styled(self.total_plays.to_string(), Style::default().fg(palette.value)),
+            Span::styled("   skipped: ", Style::default().fg(palette.text)),
+            Span::styled(self.total_skips.to_string(), Style::default().fg(palette.value)),
+        ]));
+        lines.push(Line::from(""));
+
+        let rows = height.saturating_sub(lines.len()).max(1);
+        for (i, row) in self.rows.iter().enumerate().skip(self.scroll).take(rows) {
+            let counts = format!(
+                "{:>4} plays {:>4} skips {:>8}",
+                row.plays,
+                row.skips,
+                format_hours(row.heard),
+            );
+            let mut spans = vec![
+                Span::styled(format!(" {:>3}. ", i + 1), Style::default().fg(palette.accent)),
+                Span::styled(row.name.clone(), Style::default().fg(palette.title)),
+            ];
+            let mut used = 6 + row.name.chars().count();
+            if let Some(detail) = &row.detail {
+                spans.push(Span::styled(" - ", Style::default().fg(palette.text)));
+                spans.push(Span::styled(detail.clone(), Style::default().fg(palette.highlight)));
+                used += 3 + detail.chars().count();
+            }
+            // counts lined up on the right
+            let pad = width.saturating_sub(used + counts.len() + 1).max(1);
+            spans.push(Span::raw(" ".repeat(pad)));
+            spans.push(Span::styled(counts, Style::default().fg(palette.dim)));
+            lines.push(Line::from(spans));
+        }
+        if self.rows.is_empty() {
+            lines.push(Line::from(Span::styled(
+                format!("{:pad$}nothing played yet", "", pad = width.saturating_sub(18) / 2),
+                Style::default().fg(palette.dim),
+            )));
+        }
+        Text::from(lines)
+    }
+}
+
+/// Formats seconds as 1h 05m, or m:ss under an hour
+fn format_hours(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    if seconds < 3600 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+
+/// Seconds of input in half a window, also the output hop
+const HOP_SECONDS: f64 = 0.02;
+/// Seconds a segment can move by to line up with the one before it
+const TOLERANCE_SECONDS: f64 = 0.01;
+/// Only every nth frame and offset is compared when lining up segments, plenty for finding the phase
+const SEARCH_STRIDE: usize = 2;
+const COMPARE_STRIDE: usize = 4;
+
+/// Tempo the stretch plays at, shared with the sources already in the sink
+#[derive(Clone)]
+pub struct Tempo(Arc<AtomicU32>);
+
+impl Tempo {
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicU32::new(1f32.to_bits())))
+    }
+
+    pub fn set(&self, tempo: f32) {
+        self.0.store(tempo.to_bits(), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Changes the tempo without changing the pitch (WSOLA)
+///
+/// Windowed segments of the input are overlap-added at a fixed output hop while the input
+/// advances by the hop times the tempo. Each segment is moved within the tolerance to where it
+/// best continues the previous one, so the waveforms add up in phase instead of smearing.
+/// At a tempo of 1 the segments line up exactly and the input comes out unchanged.
+pub struct Stretch<S> {
+    inner: S,
+    tempo: Tempo,
+    channels: usize,
+    /// Output hop and half the window, in frames
+    hop: usize,
+    /// Frames a segment can move by
+    tolerance: usize,
+    /// Hann window, its two halves add up to 1
+    window: Vec<f32>,
+    /// Interleaved input not yet used by every segment that will need it
+    input: Vec<f32>,
+    /// Frame number of the first frame in `input`
+    input_start: i64,
+    /// Whether the inner source ran out
+    ended: bool,
+    /// Where the next segment would start without lining up, in input frames
+    nominal: f64,
+    /// Where the last segment started
+    previous: i64,
+    /// Second half of the last windowed segment, added to the first half of the next one
+    overlap: Vec<f32>,
+    /// Interleaved samples ready to be played
+    output: VecDeque<f32>,
+    /// Whether `overlap` has been filled since the start or the last seek
+    primed: bool,
+    finished: bool,
+}
+
+impl<S> Stretch<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(inner: S, tempo: Tempo) -> Self {
+        let channels = inner.channels().max(1) as usize;
+        let sample_rate = inner.sample_rate() as f64;
+        let hop = ((sample_rate * HOP_SECONDS) as usize).max(16);
+        let tolerance = (sample_rate * TOLERANCE_SECONDS) as usize;
+        let window = (0..hop * 2)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (hop * 2) as f32).cos())
+            .collect();
+        let mut stretch = Self {
+            inner,
+            tempo,
+            channels,
+            hop,
+            tolerance,
+            window,
+            input: Vec::new(),
+            input_start: 0,
+            ended: false,
+            nominal: 0.0,
+            previous: 0,
+            overlap: Vec::new(),
+            output: VecDeque::new(),
+            primed: false,
+            finished: false,
+        };
+        stretch.reset(0);
+        stretch
+    }
+
+    /// Starts over from an input frame
+    fn reset(&mut self, frame: i64) {
+        self.input.clear();
+        self.input_start = frame;
+        self.ended = false;
+        // as if a segment had started a hop earlier, so the first one continues it
+        self.previous = frame - self.hop as i64;
+        self.nominal = self.previous as f64;
+        self.overlap.clear();
+        self.output.clear();
+        self.primed = false;
+        self.finished = false;
+    }
+
+    fn input_end(&self) -> i64 {
+        self.input_start + (self.input.len() / self.channels) as i64
+    }
+
+    /// Reads the inner source until `frame` is buffered, or it runs out
+    fn fill(&mut self, frame: i64) {
+        while !self.ended && self.input_end() < frame {
+            for _ in 0..self.channels {
+                match self.inner.next() {
+                    Some(sample) => self.input.push(sample.to_f32()),
+                    None => {
+                        self.ended = true;
+                        break;
+                    }
+                }
+            }
+        }
+        // a partial last frame would shift the channels
+        self.input.truncate(self.input.len() / self.channels * self.channels);
+    }
+
+    /// Sample of an input frame, silence outside of what's buffered
+    fn sample(&self, frame: i64, channel: usize) -> f32 {
+        let index = (frame - self.input_start) * self.channels as i64 + channel as i64;
+        if index < 0 {
+            return 0.0;
+        }
+        self.input.get(index as usize).copied().unwrap_or(0.0)
+    }
+
+    fn mono(&self, frame: i64) -> f32 {
+        (0..self.channels).map(|channel| self.sample(frame, channel)).sum()
+    }
+
+    /// Start near `target` whose first half best matches the frames starting at `natural`
+    fn best_start(&self, target: i64, natural: i64) -> i64 {
+        let tolerance = self.tolerance as i64;
+        let mut best = (f32::MIN, target);
+        for start in (target - tolerance..=target + tolerance).step_by(SEARCH_STRIDE) {
+            if start < self.input_start {
+                continue;
+            }
+            let mut correlation = 0.0;
+            let mut energy = 1e-9;
+            for i in (0..self.hop as i64).step_by(COMPARE_STRIDE) {
+                let candidate = self.mono(start + i);
+                correlation += self.mono(natural + i) * candidate;
+                energy += candidate * candidate;
+            }
+            let score = correlation / energy.sqrt();
+            if score > best.0 {
+                best = (score, start);
+            }
+        }
+        best.1
+    }
+
+    /// Windowed segment starting at an input frame
+    fn segment(&self, start: i64) -> Vec<f32> {
+        let mut segment = Vec::with_capacity(self.hop * 2 * self.channels);
+        for i in 0..self.hop * 2 {
+            for channel in 0..self.channels {
+                segment.push(self.sample(start + i as i64, channel) * self.window[i]);
+            }
+        }
+        segment
+    }
+
+    /// Adds the next segment, making another hop of output, false once the input is used up
+    fn step(&mut self) -> bool {
+        let hop = self.hop as i64;
+        if !self.primed {
+            self.primed = true;
+            self.fill(self.input_start + hop * 2);
+            self.overlap = self.segment(self.previous).split_off(self.hop * self.channels);
+        }
+        let natural = self.previous + hop;
+        if self.ended && natural >= self.input_end() {
+            return false;
+        }
+
+        let tempo = self.tempo.get() as f64;
+        self.nominal += hop as f64 * tempo;
+        let target = self.nominal.round() as i64;
+        self.fill(natural.max(target + self.tolerance as i64) + hop * 2);
+        let start = if tempo == 1.0 {
+            // lines up exactly, and any search could only make it worse
+            target
+        } else {
+            self.best_start(target, natural)
+        };
+
+        let mut segment = self.segment(start);
+        let second_half = segment.split_off(self.hop * self.channels);
+        for (sample, overlap) in segment.iter().zip(&self.overlap) {
+            self.output.push_back(sample + overlap);
+        }
+        self.overlap = second_half;
+        self.previous = start;
+
+        // nothing before this is needed by the next segment or its search
+        let keep_from = (self.previous + hop).min(self.nominal as i64 - self.tolerance as i64);
+        let drop = ((keep_from - self.input_start).max(0) as usize).min(self.input.len() / self.channels);
+        self.input.drain(..drop * self.channels);
+        self.input_start += drop as i64;
+        true
+    }
+}
+
+impl<S> Iterator for Stretch<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        while self.output.is_empty() {
+            if self.finished {
+                return None;
+            }
+            if !self.step() {
+                // the last segment fades out
+                self.output.extend(self.overlap.drain(..));
+                self.finished = true;
+            }
+        }
+        self.output.pop_front()
+    }
+}
+
+impl<S> Source for Stretch<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        let frame = (pos.as_secs_f64() * self.inner.sample_rate() as f64) as i64;
+        self.reset(frame);
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+use std::f32::consts::FRAC_PI_2;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use rodio::source::{SeekError, UniformSourceIterator};
+use rodio::cpal::FromSample;
+use rodio::{Sample, Source};
+
+/// End point of a source that's already in the sink, so it can still be cut short
+#[derive(Clone)]
+pub struct CutHandle(Arc<AtomicU64>);
+
+impl CutHandle {
+    const NONE: u64 = u64::MAX;
+
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicU64::new(Self::NONE)))
+    }
+
+    /// Ends the source once it reaches this position, straight away if it's already past it
+    pub fn cut_at(&self, position: Duration) {
+        self.0.store(position.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Lets the source play to its end again
+    pub fn clear(&self) {
+        self.0.store(Self::NONE, Ordering::Relaxed);
+    }
+}
+
+/// Source that ends wherever its CutHandle says
+pub struct Cut<S> {
+    inner: S,
+    end: CutHandle,
+    /// Samples played so far, counting from the start of the song
+    samples: u64,
+}
+
+impl<S> Cut<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(inner: S, end: CutHandle) -> Self {
+        Self { inner, end, samples: 0 }
+    }
+
+    fn samples_per_second(&self) -> u64 {
+        self.inner.sample_rate() as u64 * self.inner.channels() as u64
+    }
+}
+
+impl<S> Iterator for Cut<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        let end = self.end.0.load(Ordering::Relaxed);
+        // compared in microseconds so the end lands on the same sample the crossfade tail starts from
+        if end != CutHandle::NONE && self.samples * 1_000_000 >= end * self.samples_per_second() {
+            return None;
+        }
+        self.samples += 1;
+        self.inner.next()
+    }
+}
+
+impl<S> Source for Cut<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        let samples = (pos.as_secs_f64() * self.samples_per_second() as f64) as u64;
+        // keep to whole frames, so the channels don't swap
+        self.samples = samples - samples % self.inner.channels().max(1) as u64;
+        Ok(())
+    }
+}
+
+/// The end of one song faded out over the start of the next
+pub struct Crossfade<O, I>
+where
+    O: Source,
+    O::Item: Sample,
+{
+    /// Tail of the previous song, converted to the format of the next one, None once the fade is over
+    outgoing: Option<UniformSourceIterator<O, f32>>,
+    incoming: I,
+    /// Length of the fade, in samples of the incoming song
+    length: u64,
+    /// Samples played so far
+    position: u64,
+}
+
+impl<O, I> Crossfade<O, I>
+where
+    O: Source,
+    O::Item: Sample,
+    f32: FromSample<O::Item>,
+    I: Source,
+    I::Item: Sample,
+{
+    pub fn new(outgoing: O, incoming: I, duration: Duration) -> Self {
+        let channels = incoming.channels();
+        let sample_rate = incoming.sample_rate();
+        let length = (duration.as_secs_f64() * sample_rate as f64 * channels as f64) as u64;
+        Self {
+            outgoing: Some(UniformSourceIterator::new(outgoing, channels, sample_rate)),
+            incoming,
+            length,
+            position: 0,
+        }
+    }
+}
+
+impl<O, I> Iterator for Crossfade<O, I>
+where
+    O: Source,
+    O::Item: Sample,
+    f32: FromSample<O::Item>,
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let incoming = self.incoming.next()?.to_f32();
+        if self.position >= self.length {
+            self.outgoing = None;
+            return Some(incoming);
+        }
+        let Some(outgoing) = &mut self.outgoing else {
+            return Some(incoming);
+        };
+        // equal power, so the loudness doesn't dip in the middle
+        let t = self.position as f32 / self.length as f32 * FRAC_PI_2;
+        self.position += 1;
+        let tail = outgoing.next().unwrap_or(0.0);
+        Some(tail * t.cos() + incoming * t.sin())
+    }
+}
+
+impl<O, I> Source for Crossfade<O, I>
+where
+    O: Source,
+    O::Item: Sample,
+    f32: FromSample<O::Item>,
+    I: Source,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.incoming.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.incoming.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.incoming.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.incoming.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // seeking ends the fade, the previous song is gone by then
+        self.incoming.try_seek(pos)?;
+        self.outgoing = None;
+        self.position = self.length;
+        Ok(())
+    }
+}
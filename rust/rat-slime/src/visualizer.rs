@@ -0,0 +1,465 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span, Text},
+};
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use crate::config::palette;
+
+/// Frames kept in the ring buffer, ~0.7s at 44.1khz
+const BUFFER_FRAMES: usize = 1 << 15;
+/// Frames the tap collects before locking the shared buffer
+const TAP_BATCH: usize = 256;
+/// How many samples to analyze for the spectrum
+const FFT_SIZE: usize = 2048;
+/// Spectrum resolution, resampled to the pane width when drawn
+const BARS: usize = 256;
+/// Frames copied for the oscilloscope, enough to find a trigger and fill the pane at 2x speed
+const SCOPE_FRAMES: usize = 4096;
+/// Source frames per half character column in the oscilloscope at 1x speed
+const SCOPE_ZOOM: f32 = 2.0;
+
+/// Lowest and highest frequency shown in the spectrum
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 20000.0;
+/// Levels below this are drawn as empty
+const MIN_DB: f32 = -60.0;
+
+/// Ring buffer of the most recent decoded frames of the playing song, mixed down to mono
+pub struct SampleBuffer {
+    /// Mono samples (-1..1), the next frame goes to `written % len`
+    samples: Vec<f32>,
+    /// Frames written since the song started (or since the last seek target)
+    written: u64,
+    /// Sample rate of the song being written
+    sample_rate: u32,
+}
+
+impl SampleBuffer {
+    pub fn shared() -> Arc<Mutex<SampleBuffer>> {
+        Arc::new(Mutex::new(SampleBuffer {
+            samples: vec![0.0; BUFFER_FRAMES],
+            written: 0,
+            sample_rate: 44100,
+        }))
+    }
+
+    /// Clears the buffer for a new song or a seek to `position`
+    pub fn reset(&mut self, sample_rate: u32, position: Duration) {
+        self.samples.fill(0.0);
+        self.sample_rate = sample_rate.max(1);
+        self.written = (position.as_secs_f64() * self.sample_rate as f64) as u64;
+    }
+
+    fn push(&mut self, frames: &[f32]) {
+        let len = self.samples.len();
+        for &frame in frames {
+            self.samples[(self.written % len as u64) as usize] = frame;
+            self.written += 1;
+        }
+    }
+
+    /// Copies the newest `count` frames into `out`, oldest first
+    fn latest(&self, out: &mut Vec<f32>, count: usize) {
+        let len = self.samples.len();
+        let count = count.min(len);
+        out.clear();
+        // silence before the start of the song
+        let missing = count.saturating_sub(self.written as usize);
+        out.resize(missing, 0.0);
+        for i in self.written - (count - missing) as u64..self.written {
+            out.push(self.samples[(i % len as u64) as usize]);
+        }
+    }
+
+    /// Position in the song of the newest frame, unaffected by the sink speed
+    pub fn position(&self) -> Duration {
+        Duration::from_secs_f64(self.written as f64 / self.sample_rate as f64)
+    }
+}
+
+/// Source wrapper that copies every decoded frame into a SampleBuffer on its way to the sink
+pub struct Tap<S> {
+    inner: S,
+    buffer: Arc<Mutex<SampleBuffer>>,
+    /// Frames not yet pushed to the shared buffer
+    pending: Vec<f32>,
+    /// Channel index of the next sample
+    channel: u16,
+    /// Sum of the current frame's channels
+    frame: f32,
+    /// Whether the buffer has been reset for this source yet
+    started: bool,
+}
+
+impl<S> Tap<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(inner: S, buffer: Arc<Mutex<SampleBuffer>>) -> Self {
+        Self {
+            inner,
+            buffer,
+            pending: Vec::with_capacity(TAP_BATCH),
+            channel: 0,
+            frame: 0.0,
+            started: false,
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Ok(mut buffer) = self.buffer.lock() {
+            buffer.push(&self.pending);
+        }
+        self.pending.clear();
+    }
+}
+
+impl<S> Iterator for Tap<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        // reset on the first sample, not on creation, so a source queued ahead doesn't clear the playing one
+        if !self.started {
+            self.started = true;
+            if let Ok(mut buffer) = self.buffer.lock() {
+                buffer.reset(self.inner.sample_rate(), Duration::ZERO);
+            }
+        }
+        let channels = self.inner.channels().max(1);
+        let sample = match self.inner.next() {
+            Some(sample) => sample,
+            None => {
+                self.flush();
+                return None;
+            }
+        };
+
+        self.frame += sample.to_f32();
+        self.channel += 1;
+        if self.channel >= channels {
+            self.pending.push(self.frame / channels as f32);
+            self.frame = 0.0;
+            self.channel = 0;
+            if self.pending.len() >= TAP_BATCH {
+                self.flush();
+            }
+        }
+        Some(sample)
+    }
+}
+
+impl<S> Source for Tap<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.pending.clear();
+        self.frame = 0.0;
+        self.channel = 0;
+        self.started = true;
+        if let Ok(mut buffer) = self.buffer.lock() {
+            buffer.reset(self.inner.sample_rate(), pos);
+        }
+        Ok(())
+    }
+}
+
+/// Spectrum and oscilloscope state, updated once per frame from the SampleBuffer
+pub struct Visualizer {
+    buffer: Arc<Mutex<SampleBuffer>>,
+    fft: Arc<dyn Fft<f32>>,
+    /// Hann window to reduce spectral leakage
+    window: Vec<f32>,
+    /// Scratch copy of the newest frames
+    samples: Vec<f32>,
+    /// Smoothed bar heights (0-1), log spaced from MIN_FREQ to MAX_FREQ
+    bars: Vec<f32>,
+    /// Waveform for the oscilloscope, starting at the trigger point
+    scope: Vec<f32>,
+    /// Peak of the oscilloscope window, for scaling
+    scope_peak: f32,
+    /// RMS and peak level of the newest frames (0-1)
+    rms: f32,
+    peak: f32,
+    /// Playback speed the last update was made with
+    speed: f32,
+    sample_rate: u32,
+    /// `written` of the buffer at the last update, to skip updates without new audio
+    last_written: u64,
+    last_update: Instant,
+}
+
+impl Visualizer {
+    pub fn new(buffer: Arc<Mutex<SampleBuffer>>) -> Self {
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(FFT_SIZE);
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 * (1.0 - (2.0 * std::f32::consts::PI * i as f32 / (FFT_SIZE - 1) as f32).cos()))
+            .collect();
+        Self {
+            buffer,
+            fft,
+            window,
+            samples: Vec::with_capacity(SCOPE_FRAMES.max(FFT_SIZE)),
+            bars: vec![0.0; BARS],
+            scope: Vec::new(),
+            scope_peak: 0.0,
+            rms: 0.0,
+            peak: 0.0,
+            speed: 1.0,
+            sample_rate: 44100,
+            last_written: 0,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn update(&mut self, speed: f32) {
+        let elapsed = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+        // 5% falloff per frame at 60fps, independent of the actual frame rate
+        let falloff = 0.95_f32.powf(elapsed * 60.0);
+
+        let written = {
+            let buffer = self.buffer.lock().unwrap();
+            if buffer.written != self.last_written {
+                buffer.latest(&mut self.samples, SCOPE_FRAMES.max(FFT_SIZE));
+                self.sample_rate = buffer.sample_rate;
+            }
+            buffer.written
+        };
+        if written == self.last_written {
+            // paused or nothing playing, let the bars fall
+            for bar in self.bars.iter_mut() {
+                *bar *= falloff;
+            }
+            self.rms *= falloff;
+            self.peak *= falloff;
+            return;
+        }
+        self.last_written = written;
+        self.speed = speed.max(0.1);
+
+        self.update_spectrum(falloff);
+        self.update_scope();
+    }
+
+    fn update_spectrum(&mut self, falloff: f32) {
+        let fft_samples = &self.samples[self.samples.len() - FFT_SIZE..];
+        let mut buffer: Vec<Complex<f32>> = fft_samples
+            .iter()
+            .zip(&self.window)
+            .map(|(&sample, &win_val)| Complex::new(sample * win_val, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+        let bins: Vec<f32> = buffer.iter().take(FFT_SIZE / 2).map(|bin| bin.norm()).collect();
+
+        // at a different speed every frequency is heard shifted by the same factor
+        let bin_resolution = self.sample_rate as f32 * self.speed / FFT_SIZE as f32;
+        let log_scale = (MAX_FREQ / MIN_FREQ).ln();
+        for i in 0..BARS {
+            let f_start = MIN_FREQ * (log_scale * (i as f32 / BARS as f32)).exp();
+            let f_end = MIN_FREQ * (log_scale * ((i + 1) as f32 / BARS as f32)).exp();
+            let start = (f_start / bin_resolution).floor() as usize;
+            let end = ((f_end / bin_resolution).ceil() as usize).min(bins.len());
+            let magnitude = if end > start + 1 {
+                // peak of the bins this bar covers
+                bins[start..end].iter().fold(0.0f32, |a, &b| a.max(b))
+            } else {
+                interpolated_magnitude(&bins, (f_start + f_end) / 2.0 / bin_resolution)
+            };
+
+            // a full scale sine under a hann window peaks at FFT_SIZE / 4
+            let scaled = magnitude / (FFT_SIZE as f32 / 4.0);
+            let db = 20.0 * (scaled + 1e-6).log10();
+            let height = ((db - MIN_DB) / -MIN_DB).clamp(0.0, 1.0);
+            self.bars[i] = height.max(self.bars[i] * falloff);
+        }
+
+        let recent = &fft_samples[FFT_SIZE / 2..];
+        let sum_sq: f32 = recent.iter().map(|s| s * s).sum();
+        let rms = (sum_sq / recent.len() as f32).sqrt();
+        let peak = recent.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+        self.rms = rms.max(self.rms * falloff);
+        self.peak = peak.max(self.peak * falloff);
+    }
+
+    fn update_scope(&mut self) {
+        // lowpassed copy to find a stable trigger point, the raw samples are drawn
+        let smoothing = 0.05;
+        let mut smoothed = Vec::with_capacity(self.samples.len());
+        let mut current_value = self.samples[0];
+        for &sample in &self.samples {
+            current_value += (sample - current_value) * smoothing;
+            smoothed.push(current_value);
+        }
+
+        // start far enough back that the whole window fits after the trigger
+        let visible = (SCOPE_FRAMES as f32 / 2.0) as usize;
+        let search_end = self.samples.len().saturating_sub(visible);
+        let max_sample = smoothed.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+        let noise_gap = max_sample * 0.2; // ignore small fluctuations around zero
+        let mut crossed = false; // have we dipped below the noise floor
+        let mut trigger = search_end.saturating_sub(visible);
+        for i in trigger..search_end.saturating_sub(1) {
+            if !crossed && smoothed[i] < -noise_gap {
+                crossed = true;
+            }
+            if crossed && smoothed[i] <= noise_gap && smoothed[i + 1] > noise_gap {
+                trigger = i;
+                break;
+            }
+        }
+
+        self.scope.clear();
+        self.scope.extend_from_slice(&self.samples[trigger..]);
+        self.scope_peak = self.scope.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+    }
+
+    /// Log frequency bars, drawn bottom up with eighth blocks
+    pub fn spectrum(&self, width: usize, height: usize) -> Text<'static> {
+        const BLOCKS: [&str; 9] = [" ", "▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+        if width == 0 || height == 0 {
+            return Text::default();
+        }
+        let heights: Vec<f32> = (0..width)
+            .map(|x| {
+                let index = x as f32 * (BARS - 1) as f32 / (width.max(2) - 1) as f32;
+                interpolated_magnitude(&self.bars, index).max(self.bars[index as usize]) * height as f32
+            })
+            .collect();
+
+        let mut lines = Vec::with_capacity(height);
+        for row in (0..height).rev() {
+            let color = gradient(row as f32 / height as f32);
+            let mut text = String::with_capacity(width * 3);
+            for &bar_height in &heights {
+                let fill = ((bar_height - row as f32) * 8.0).clamp(0.0, 8.0) as usize;
+                text.push_str(BLOCKS[fill]);
+            }
+            lines.push(Line::from(Span::styled(text, Style::default().fg(color))));
+        }
+        Text::from(lines)
+    }
+
+    /// Triggered waveform, two samples per character column and two rows per character
+    pub fn oscilloscope(&self, width: usize, height: usize) -> Text<'static> {
+        // characters for rendering the waveform based on which quadrants of the cell are filled
+        const QUADRANTS: [&str; 16] = [
+            " ", "▗", "▖", "▄", "▝", "▐", "▞", "▟", "▘", "▚", "▌", "▙", "▀", "▜", "▛", "█",
+        ];
+        if width == 0 || height == 0 || self.scope.is_empty() {
+            return Text::default();
+        }
+        let mut masks = vec![0u8; width * height];
+        let half_rows = (height * 2) as f32;
+        let middle = half_rows / 2.0;
+        // scale to the window peak, but don't blow up near silence
+        let gain = 1.0 / self.scope_peak.max(0.1);
+        // at a higher speed more of the song passes through the same space
+        let step = SCOPE_ZOOM * self.speed;
+
+        for half_column in 0..width * 2 {
+            let index = (half_column as f32 * step) as usize;
+            let Some(&sample) = self.scope.get(index) else {
+                break;
+            };
+            // half row of the sample, 0 at the top
+            let target = (middle - sample * gain * middle).clamp(0.0, half_rows - 1.0) as usize;
+            let middle = middle as usize;
+            let (from, to) = if target < middle { (target, middle - 1) } else { (middle, target) };
+            let x = half_column / 2;
+            let right = half_column % 2 == 1;
+            for half_row in from..=to {
+                let y = half_row / 2;
+                let lower = half_row % 2 == 1;
+                let bit = match (lower, right) {
+                    (false, false) => 0b1000, // UL
+                    (false, true) => 0b0100, // UR
+                    (true, false) => 0b0010, // LL
+                    (true, true) => 0b0001, // LR
+                };
+                masks[y * width + x] |= bit;
+            }
+        }
+
+        let lines = masks
+            .chunks(width)
+            .map(|row| Line::from(row.iter().map(|&mask| QUADRANTS[mask as usize]).collect::<String>()))
+            .collect::<Vec<Line>>();
+        Text::from(lines).style(Style::default().fg(palette().title))
+    }
+
+    /// RMS and peak level meters in dB
+    pub fn levels(&self, width: usize, height: usize) -> Text<'static> {
+        let bar_width = width.saturating_sub(16);
+        let meter = |name: &str, level: f32| {
+            let db = 20.0 * (level + 1e-6).log10();
+            let fill = ((db - MIN_DB) / -MIN_DB).clamp(0.0, 1.0);
+            let filled = (fill * bar_width as f32).round() as usize;
+            Line::from(vec![
+                Span::styled(format!(" {name:<5}"), Style::default().fg(palette().text)),
+                Span::styled("█".repeat(filled), Style::default().fg(gradient(fill))),
+                Span::raw(" ".repeat(bar_width - filled)),
+                Span::styled(format!(" {:>5.1}dB", db.max(MIN_DB)), Style::default().fg(palette().value)),
+            ])
+        };
+
+        let mut lines = Vec::new();
+        for _ in 0..height.saturating_sub(2) / 2 {
+            lines.push(Line::from(""));
+        }
+        lines.push(meter("rms", self.rms));
+        lines.push(meter("peak", self.peak));
+        Text::from(lines)
+    }
+}
+
+/// Interpolated magnitude for a non-integer bin index
+fn interpolated_magnitude(bins: &[f32], index: f32) -> f32 {
+    let len = bins.len() as f32;
+    if index < 0.0 || index >= len - 1.0 {
+        return 0.0;
+    }
+    let floor_idx = index.floor() as usize;
+    let t = index - floor_idx as f32; // fractional part (0.0 to 1.0)
+    bins[floor_idx] * (1.0 - t) + bins[floor_idx + 1] * t
+}
+
+/// Cyan at the bottom, through magenta to red at the top (0-1)
+fn gradient(t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    if t < 0.6 {
+        let k = t / 0.6;
+        Color::Rgb((80.0 + 175.0 * k) as u8, (220.0 - 140.0 * k) as u8, 255)
+    } else {
+        let k = (t - 0.6) / 0.4;
+        Color::Rgb(255, (80.0 - 20.0 * k) as u8, (255.0 - 175.0 * k) as u8)
+    }
+}